@@ -1,6 +1,10 @@
-use types::{Point, Index, Radius};
-use config::Config;
-use EPSILON;
+use types::{Point, Index, Radius, cmp_f64};
+use config::{Config, ConfigProblem};
+use geometry;
+use geometry::Segment;
+pub use geometry::Measures;
+use hull::ConvexHull;
+use predicates::orient2d;
 
 use std::f64;
 
@@ -8,21 +12,20 @@ use std::f64;
 
 use self::Orientation::*;
 #[derive(PartialEq, Clone, Debug)]
-enum Orientation {
+pub enum Orientation {
     Colinear,
     Clockwise,
     CounterClockwise
 }
-fn orientation(a: Point, b: Point, c: Point) -> Orientation {
+/// Exact, whatever the scale of the points, so only truly colinear
+/// points are Colinear.
+pub fn orientation(a: Point, b: Point, c: Point) -> Orientation {
     // println!("a: {:?}, b:{:?}, c:{:?}", a, b, c);
-    let ab = b - a;
-    let ac = c - a;
-    // println!("ab: {:?}, ac: {:?}", ab, ac);
-    let cross = ab.y * ac.x - ab.x * ac.y;
-    // println!("Cross: {}", cross);
-    if cross.abs() < EPSILON {
+    let det = orient2d(a, b, c);
+    // println!("Det: {}", det);
+    if det == 0.0 {
         return Colinear;
-    } else if cross > 0.0 {
+    } else if det < 0.0 {
         return Clockwise;
     } else {
         return CounterClockwise;
@@ -64,6 +67,20 @@ fn orientation_test_colinear() {
 
     assert_eq!(orientation(a,b,c), Colinear);
 }
+#[test]
+fn orientation_test_scale() {
+    // Tiny triangles still have an orientation.
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(0.0, 0.001);
+    let c = Point::new(0.001, 0.0);
+    assert_eq!(orientation(a,b,c), Clockwise);
+
+    // And big colinear points are still colinear.
+    let a = Point::new(151.82335851231093, 558.7649418094913);
+    let b = Point::new(2. * a.x, 2. * a.y);
+    let c = Point::new(4. * a.x, 4. * a.y);
+    assert_eq!(orientation(a,b,c), Colinear);
+}
 
 /// Giftwrap algorithm for finding the convex hull of a set of points.
 /// Runs in O(n * m), where n = included.len() and m = result.len();
@@ -72,6 +89,7 @@ pub fn giftwrap(points: &Vec<Point>,
         -> Vec<Index> { // Hull
 
     use std::collections::HashSet;
+    use na::Norm;
 
     if included.len() <= 2 {
         return included.clone();
@@ -113,7 +131,14 @@ pub fn giftwrap(points: &Vec<Point>,
         };
         for &other_ix in ix_left_to_insert.iter().skip(1) {
             match orientation(points[base_ix], points[end_ix], points[other_ix]) {
-                Colinear => continue, // TODO check distance
+                Colinear => {
+                    // Take the farthest, so we skip the ones in the middle.
+                    let end_dist = (points[end_ix] - points[base_ix]).sqnorm();
+                    let other_dist = (points[other_ix] - points[base_ix]).sqnorm();
+                    if other_dist > end_dist {
+                        end_ix = other_ix;
+                    }
+                },
                 Clockwise => continue,
                 CounterClockwise => {
                     /*
@@ -178,41 +203,219 @@ fn test_giftwrap_torus() {
     let hull = giftwrap(&points, &inpoints);
     assert_eq!(hull, vec![ 1, 2, 3, 4, 5, 6, 7, 8, ]);
 }
+#[test]
+fn test_giftwrap_colinear() {
+    let points = vec![
+        Point::new(0.0, 0.0), // 0
+        Point::new(0.0, 1.0), // 1
+        Point::new(0.0, 2.0), // 2
+        Point::new(1.0, 2.0), // 3
+        Point::new(2.0, 2.0), // 4
+        Point::new(2.0, 0.0), // 5
+    ];
+    let inpoints = vec![5,4,3,2,1,0];
+    let hull = giftwrap(&points, &inpoints);
+    assert_eq!(hull, vec![ 2, 4, 5, 0 ]);
+}
+
+
+/// The blob found for one set of points.
+/// The hull goes clockwise round the blob, and every point gets a radius,
+/// so the outline runs round the discs of the hull points,
+/// inside for points in the blob and outside for the rest.
+pub struct Blob<'a> {
+    pub points: &'a Vec<Point>,
+    pub inblob: Vec<bool>,
+    pub hull: Vec<Index>,
+    pub radii: Vec<Radius>,
+}
+
+impl<'a> Blob<'a> {
+    /// Whether the point at ix is one of the set.
+    pub fn is_in(&self, ix: Index) -> bool {
+        self.inblob[ix]
+    }
+
+    /// The points in the set.
+    pub fn inpoints(&self) -> Vec<Index> {
+        (0..self.points.len()).filter(|&ix| self.inblob[ix]).collect()
+    }
 
+    /// The points left out of the set.
+    pub fn expoints(&self) -> Vec<Index> {
+        (0..self.points.len()).filter(|&ix| !self.inblob[ix]).collect()
+    }
+
+    /// The smallest radius of the points on the hull,
+    /// which is how sharp the outline can turn.
+    pub fn smallest_radius(&self) -> Radius {
+        self.hull.iter()
+            .map(|&ix| self.radii[ix])
+            .fold(f64::INFINITY, |a, b| a.min(b))
+    }
+
+    /// The exact outline, as arcs joined by tangent lines.
+    pub fn segments(&self) -> Vec<Segment> {
+        geometry::outline(self)
+    }
+
+    /// Whether p is inside the blob's smooth outline.
+    pub fn contains(&self, p: &Point) -> bool {
+        geometry::winding_number(&self.segments(), p) != 0
+    }
+
+    /// How far p is from the blob's smooth outline, negative inside it.
+    pub fn signed_distance(&self, p: &Point) -> f64 {
+        geometry::signed_distance(&self.segments(), p)
+    }
+
+    /// The area, perimeter and centroid of the blob's smooth shape.
+    pub fn measures(&self) -> Measures {
+        geometry::measures(&self.segments())
+    }
+
+    /// The area, perimeter and centroid of the convex hull of the in-points,
+    /// as algorithm finds it, before any dents are made for the ex-points.
+    pub fn hull_measures(&self, algorithm: &ConvexHull) -> Measures {
+        geometry::polygon_measures(self.points, &algorithm.hull(self.points, &self.inpoints()))
+    }
+
+    /// The outline as a polygon going clockwise,
+    /// less than tolerance from the smooth shape.
+    pub fn outline(&self, tolerance: f64) -> Vec<Point> {
+        geometry::polyline(self, tolerance)
+    }
+}
+#[test]
+fn test_blob_queries() {
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.), Point::new(0., 1.)];
+    let blob = Blob{ points: &points, inblob: vec![true, false, true],
+                     hull: vec![0, 2, 1], radii: vec![0.25, 0.1, 0.2] };
+    assert!(blob.is_in(2) && !blob.is_in(1));
+    assert_eq!(blob.inpoints(), vec![0, 2]);
+    assert_eq!(blob.expoints(), vec![1]);
+    assert_eq!(blob.smallest_radius(), 0.1);
+}
 
 /// The main workhorse function.
-/// Finds the perfect hull, and radii for the blob.
-pub fn find_hull(
+/// Finds the perfect hull, and radii for the blob around inpoints.
+/// Fails if the config names a hull algorithm that doesn't exist.
+pub fn find_hull<'a>(
             config: &Config,
-            points: &Vec<Point>,
-            inblob: &Vec<bool>,
+            points: &'a Vec<Point>,
             inpoints: &Vec<Index>,
-            expoints: &Vec<Index>,
-            ) -> (Vec<Index>, Vec<Radius>) {
+            nearest: &Vec<Radius>,
+            ) -> Result<Blob<'a>, ConfigProblem> {
+    let inblob = make_inblob(points.len(), inpoints);
+    let expoints: Vec<Index> = (0..points.len()).filter(|&ex| !inblob[ex]).collect();
 
-    let mut hull = giftwrap(&points, &inpoints);
+    let algorithm = try!(config.hull_algorithm());
+    let mut hull = algorithm.hull(&points, &inpoints);
 
-    debug!("After giftwrap");
+    debug!("After {}", config.run.hull_algorithm);
     if config.run.fix_hull {
-        // todo fix hull
         hull = fix_hull(&points, &inblob, hull, &inpoints, &expoints);
     }
     debug!("After fix_hull");
     if config.run.refine_poly {
-        // todo refine poly
+        hull = refine_poly(&points, &inblob, hull, &inpoints, &expoints,
+                           config.b2.refine_epsilon);
     }
     debug!("After refine_poly");
     if config.run.rm_crossing {
-        // todo remove crossing
+        hull = rm_crossing(&points, hull);
     }
     debug!("After rm_crossings");
-    let dist = compute_nearest_distances(&points);
-    trace!("Distances {:?}", dist);
-    let radii = dist.into_iter().map(|x| x / config.b2.mindist_radius_factor).collect();
+    trace!("Distances {:?}", nearest);
+    let mut radii = nearest.iter().map(|x| x / config.b2.mindist_radius_factor).collect();
+    let shrunk = fit_radii(&points, &hull, &inblob, &mut radii);
+    if !shrunk.is_empty() {
+        warn!("Shrunk the radii of {:?} to fit the hull", shrunk);
+    }
     trace!("Radii {:?}", radii);
     debug!("After compute radii");
 
-    (hull, radii)
+    Ok(Blob{ points: points, inblob: inblob, hull: hull, radii: radii })
+}
+
+// Radii that are too big get fit a little inside the limit,
+// so rounding can't push them back over it.
+const RADIUS_SLACK: f64 = 0.999;
+
+/// Shrinks radii until every edge of the hull has a tangent line,
+/// as smooth_line_normal needs.
+/// Discs on opposite sides of the outline must not overlap,
+/// and discs on the same side must not swallow one another.
+/// Returns the points whose radii were shrunk.
+pub fn fit_radii(points: &Vec<Point>,
+                 hull: &Vec<Index>,
+                 inblob: &Vec<bool>,
+                 radii: &mut Vec<Radius>) -> Vec<Index> {
+    use na::Norm;
+    let mut shrunk = vec![];
+    // Shrinking a radius can break the next edge along,
+    // so go round until nothing changes.
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for hull_ix in 0..hull.len() {
+            let a = hull[hull_ix];
+            let b = hull[(hull_ix + 1) % hull.len()];
+            let distance = (points[b] - points[a]).norm();
+            let limit = distance * RADIUS_SLACK;
+
+            let mut fixed = vec![];
+            if inblob[a] != inblob[b] {
+                let total = radii[a] + radii[b];
+                if total / distance > 1. {
+                    radii[a] = radii[a] * limit / total;
+                    radii[b] = radii[b] * limit / total;
+                    fixed.push(a);
+                    fixed.push(b);
+                }
+            } else if (radii[a] - radii[b]).abs() / distance > 1. {
+                let (big, small) = if radii[a] > radii[b] { (a, b) } else { (b, a) };
+                radii[big] = radii[small] + limit;
+                fixed.push(big);
+            }
+
+            for ix in fixed {
+                changed = true;
+                if !shrunk.contains(&ix) {
+                    shrunk.push(ix);
+                }
+            }
+        }
+    }
+    shrunk
+}
+#[test]
+fn test_fit_radii() {
+    use na::Norm;
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.),
+                      Point::new(1., 1.), Point::new(0., 1.)];
+    let inblob = vec![true, false, true, true];
+    let hull = vec![0, 3, 2, 1];
+    // 0 and 1 overlap, and 3 swallows 2.
+    let mut radii = vec![0.8, 0.8, 0.1, 2.];
+    let shrunk = fit_radii(&points, &hull, &inblob, &mut radii);
+    assert_eq!(shrunk, vec![3, 1, 0]);
+    assert_eq!(radii[2], 0.1);
+    for hull_ix in 0..hull.len() {
+        let a = hull[hull_ix];
+        let b = hull[(hull_ix + 1) % hull.len()];
+        let distance = (points[b] - points[a]).norm();
+        if inblob[a] == inblob[b] {
+            assert!((radii[a] - radii[b]).abs() / distance <= 1.);
+        } else {
+            assert!((radii[a] + radii[b]) / distance <= 1.);
+        }
+    }
+
+    // Radii that fit are left alone.
+    let mut radii = vec![0.3; 4];
+    assert_eq!(fit_radii(&points, &hull, &inblob, &mut radii), vec![]);
+    assert_eq!(radii, vec![0.3; 4]);
 }
 
 pub fn make_inblob(size: usize, included: &Vec<Index>) -> Vec<bool> {
@@ -224,35 +427,26 @@ pub fn make_inblob(size: usize, included: &Vec<Index>) -> Vec<bool> {
     inblob
 }
 
-fn partial_min(a:f64, b:f64) -> f64 {
-    if a < b {
-        a
-    } else {
-        b
-    }
-}
+/// Finds the distance from each point to its nearest neighbour.
+/// This only depends on the points, so compute it once and share it
+/// between all the sets.
 pub fn compute_nearest_distances(
         points: &Vec<Point>) -> Vec<Radius> {
 
-    use na::Norm;
-    // To avoid a lot of sqrts, I compute all the radii squared
-    // and sqrt it all at the end.
-    let mut radii2 = vec![f64::INFINITY; points.len()];
-    for i in 0..points.len() {
-        for j in i+1..points.len() {
-            let sqnorm = (points[i] - points[j]).sqnorm();
-            // Nans or other incomparables will return nothing
-            // if partial_min is passed them
-            // We know sqnorm is not nan though.
-            radii2[i] = partial_min(radii2[i], sqnorm);
-            radii2[j] = partial_min(radii2[j], sqnorm);
+    use spatial::KdTree;
+    let tree = KdTree::new(points);
+    (0..points.len()).map(|i| {
+        match tree.nearest(&points[i], Some(i)) {
+            Some((_, sqnorm)) => sqnorm.sqrt(),
+            None => f64::INFINITY,
         }
-    }
-    // TODO factor out the 0.3 into config.b2.mindist_radius_factor
-    let radii = radii2.into_iter().map(|r2:f64| -> f64 {r2.sqrt()}).collect();
-    radii
+    }).collect()
 }
 
+/// Carves the excluded points out of the hull.
+/// Each excluded point inside the polygon is spliced into its nearest edge,
+/// which leaves a concave dent around it.
+/// Shallow points are carved first, as they can be in the way of deeper ones.
 pub fn fix_hull(
         points: &Vec<Point>,
         inblob: &Vec<bool>,
@@ -260,18 +454,477 @@ pub fn fix_hull(
         inpoints: &Vec<Index>,
         expoints: &Vec<Index>) -> Vec<Index> {
 
-    for &eix in expoints {
-        // TODO improve runtime by banishing in_hull.
-        if point_inside(&points, &points[eix], &hull) && !in_hull(eix, &hull) {
+    if hull.len() < 3 {
+        return hull;
+    }
+    let mut on_hull = make_inblob(points.len(), &hull);
+    let mut inside: Vec<(f64, Index)> = expoints.iter()
+        .filter(|&&eix| !on_hull[eix] && point_inside(&points, &points[eix], &hull))
+        .map(|&eix| (nearest_edge(&points, &points[eix], &hull).0, eix))
+        .collect();
+    inside.sort_by(|x, y| cmp_f64(x.0, y.0));
+    let mut still_inside = make_inblob(points.len(), &inside.iter().map(|x| x.1).collect());
+
+    // Each pass carves every point it can. A point that's blocked may not be
+    // once others have been carved, so passes go on until one carves nothing.
+    loop {
+        let mut carved = false;
+        for &(_, eix) in &inside {
+            if on_hull[eix] || !still_inside[eix] {
+                continue;
+            }
+            let edge = match insertion_edge(&points, &inblob, &hull, &on_hull, eix) {
+                Some(edge) => edge,
+                None => continue,
+            };
+            debug!("Carving {}({:?}) out after {}", eix, points[eix], hull[edge]);
+            // Only the points in the triangle the dent cuts off come out.
+            let triangle = vec![hull[edge], eix, hull[(edge + 1) % hull.len()]];
+            for &(_, other) in &inside {
+                if still_inside[other] && point_inside(&points, &points[other], &triangle) {
+                    still_inside[other] = false;
+                }
+            }
+            hull.insert(edge + 1, eix);
+            on_hull[eix] = true;
+            carved = true;
+        }
+        if !carved {
+            break;
+        }
+    }
 
+    for &(_, eix) in &inside {
+        if still_inside[eix] && !on_hull[eix] {
             warn!("There's a excluded point inside: {1:?}({0})", eix, points[eix]);
+        }
+    }
+    hull
+}
+#[test]
+fn test_fix_hull() {
+    let points = vec![
+        Point::new(0.0, 0.0), // 0
+        Point::new(0.0, 10.0), // 1
+        Point::new(10.0, 10.0), // 2
+        Point::new(10.0, 0.0), // 3
+        Point::new(5.0, 7.0), // 4, excluded
+        Point::new(5.0, 8.0), // 5, excluded, in front of 4
+        Point::new(5.0, 2.0), // 6
+    ];
+    let inpoints = vec![0,1,2,3,6];
+    let expoints = vec![4,5];
+    let inblob = make_inblob(points.len(), &inpoints);
+    let hull = giftwrap(&points, &inpoints);
 
+    let hull = fix_hull(&points, &inblob, hull, &inpoints, &expoints);
+    for &eix in &expoints {
+        assert!(hull.contains(&eix) || !point_inside(&points, &points[eix], &hull));
+    }
+    assert!(point_inside(&points, &points[6], &hull));
+    assert_eq!(find_crossing(&points, &hull), None);
+}
+#[test]
+fn test_fix_hull_datasets() {
+    use input::{read_points, read_combs};
+
+    let points = read_points("datasets/r100.dat").unwrap();
+    for combs_file in &["datasets/bad_79.txt", "datasets/merge.txt"] {
+        let combs = read_combs(combs_file, points.len()).unwrap();
+        for inpoints in combs.iter().flat_map(|comb| comb.iter()) {
+            let expoints: Vec<Index> = (0..points.len()).filter(
+                |ex| ! inpoints.iter().any(|inp| inp == ex)).collect();
+            let inblob = make_inblob(points.len(), inpoints);
+            let hull = giftwrap(&points, inpoints);
+            let hull = fix_hull(&points, &inblob, hull, inpoints, &expoints);
+            for &eix in &expoints {
+                assert!(hull.contains(&eix) || !point_inside(&points, &points[eix], &hull),
+                        "{} is still inside {:?} in {}", eix, hull, combs_file);
+            }
         }
     }
+}
+#[test]
+fn test_fix_hull_huge() {
+    use input::{read_points, read_combs};
+
+    // Most of the 10000 points are excluded, and over 1600 are inside the hull.
+    let points = read_points("datasets/huge.dat").unwrap();
+    let combs = read_combs("datasets/huge.txt", points.len()).unwrap();
+    let inpoints = &combs[0][0];
+    let inblob = make_inblob(points.len(), inpoints);
+    let expoints: Vec<Index> = (0..points.len()).filter(|&ix| !inblob[ix]).collect();
+    let hull = fix_hull(&points, &inblob, giftwrap(&points, inpoints), inpoints, &expoints);
+    let on_hull = make_inblob(points.len(), &hull);
+    for &eix in &expoints {
+        assert!(on_hull[eix] || !point_inside(&points, &points[eix], &hull));
+    }
+    assert_eq!(find_crossing(&points, &hull), None);
+}
+
+/// Refines the polygon until it separates the included points from the
+/// excluded ones by at least `epsilon`.
+/// Points that are not on the polygon must be at least `epsilon` away from
+/// it, on the correct side.  Offending points are spliced into their
+/// nearest edge, then any vertex the polygon no longer needs is removed.
+pub fn refine_poly(
+        points: &Vec<Point>,
+        inblob: &Vec<bool>,
+        mut hull: Vec<Index>,
+        inpoints: &Vec<Index>,
+        expoints: &Vec<Index>,
+        epsilon: f64) -> Vec<Index> {
+
+    if hull.len() < 3 {
+        return hull;
+    }
+
+    let mut on_hull = make_inblob(points.len(), &hull);
+    // Every pass either inserts a point that was not on the polygon,
+    // or stops, so this runs at most points.len() times.
+    loop {
+        let mut inserted = false;
+        for &ix in inpoints.iter().chain(expoints.iter()) {
+            if on_hull[ix] || is_separated(points, inblob, &hull, ix, epsilon) {
+                continue;
+            }
+            match insertion_edge(points, inblob, &hull, &on_hull, ix) {
+                Some(edge) => {
+                    debug!("Refining: inserting {}({:?}) after {}", ix, points[ix], hull[edge]);
+                    hull.insert(edge + 1, ix);
+                    on_hull[ix] = true;
+                    inserted = true;
+                },
+                None => warn!("Could not separate {1:?}({0}) from the polygon", ix, points[ix]),
+            }
+        }
+        if !inserted {
+            break;
+        }
+    }
+
+    // Now drop vertices that are far enough from the rest of the polygon
+    // to be separated without being on it.
+    loop {
+        let mut removed = false;
+        let mut hull_ix = 0;
+        while hull_ix < hull.len() && hull.len() > 3 {
+            if can_remove(points, inblob, &hull, &on_hull, hull_ix, epsilon) {
+                let ix = hull.remove(hull_ix);
+                on_hull[ix] = false;
+                debug!("Refining: removing {}({:?})", ix, points[ix]);
+                removed = true;
+            } else {
+                hull_ix += 1;
+            }
+        }
+        if !removed {
+            break;
+        }
+    }
+    hull
+}
+
+/// Can the vertex at hull_ix be dropped, joining its neighbours a and b
+/// with a straight edge, and leave every point that's off the polygon
+/// separated by epsilon?
+/// The edge ab mustn't cross the rest of the polygon. Only the points in
+/// the triangle the removal uncovers change sides, and only the ones near
+/// ab get closer to the polygon, so they're all that need checking.
+fn can_remove(points: &Vec<Point>, inblob: &Vec<bool>, hull: &Vec<Index>,
+              on_hull: &Vec<bool>, hull_ix: usize, epsilon: f64) -> bool {
+    let n = hull.len();
+    let (a_ix, ix, b_ix) = (hull[(hull_ix + n - 1) % n], hull[hull_ix], hull[(hull_ix + 1) % n]);
+    let (a, b) = (points[a_ix], points[b_ix]);
+    for other_ix in 0..n {
+        let (c_ix, d_ix) = (hull[other_ix], hull[(other_ix + 1) % n]);
+        if c_ix == a_ix || c_ix == b_ix || d_ix == a_ix || d_ix == b_ix {
+            continue;
+        }
+        if segments_cross(a, b, points[c_ix], points[d_ix]) {
+            return false;
+        }
+    }
+
+    let mut without = hull.clone();
+    without.remove(hull_ix);
+    let triangle = vec![a_ix, ix, b_ix];
+    (0..points.len())
+        .filter(|&other| other == ix || (!on_hull[other]
+                && (point_inside(points, &points[other], &triangle)
+                    || segment_distance(&points[other], &a, &b) < epsilon)))
+        .all(|other| is_separated(points, inblob, &without, other, epsilon))
+}
+#[test]
+fn test_can_remove() {
+    let points = vec![
+        Point::new(0.0, 0.0), // 0
+        Point::new(0.0, 10.0), // 1
+        Point::new(5.0, 8.0), // 2, a shallow dent
+        Point::new(10.0, 10.0), // 3
+        Point::new(10.0, 0.0), // 4
+        Point::new(5.0, 5.0), // 5
+        Point::new(5.0, 1.0), // 6, excluded, under a dent
+        Point::new(2.0, 3.0), // 7, just inside the corner at 0
+    ];
+    let inblob = vec![true, true, true, true, true, true, false, true];
+    let hull = vec![1, 2, 3, 4, 6, 0];
+    let on_hull = make_inblob(points.len(), &hull);
+    assert!(can_remove(&points, &inblob, &hull, &on_hull, 1, 1.0));
+    // Without 6 the excluded point would be inside.
+    assert!(!can_remove(&points, &inblob, &hull, &on_hull, 4, 1.0));
+    // Without 0, 7 would be outside.
+    assert!(!can_remove(&points, &inblob, &hull, &on_hull, 5, 1.0));
+}
+#[test]
+fn test_refine_poly_dent() {
+    let points = vec![
+        Point::new(0.0, 0.0), // 0
+        Point::new(0.0, 10.0), // 1
+        Point::new(10.0, 10.0), // 2
+        Point::new(10.0, 0.0), // 3
+        Point::new(5.0, 9.5), // 4, excluded, just under the top edge
+        Point::new(5.0, 5.0), // 5
+    ];
+    let inpoints = vec![0,1,2,3,5];
+    let expoints = vec![4];
+    let inblob = make_inblob(points.len(), &inpoints);
+    let hull = giftwrap(&points, &inpoints);
+
+    let hull = refine_poly(&points, &inblob, hull, &inpoints, &expoints, 1.0);
+    assert_eq!(hull, vec![1, 4, 2, 3, 0]);
+    assert!(is_separated(&points, &inblob, &hull, 5, 1.0));
+}
+#[test]
+fn test_refine_poly_margin() {
+    let points = vec![
+        Point::new(0.0, 0.0), // 0
+        Point::new(0.0, 10.0), // 1
+        Point::new(10.0, 10.0), // 2
+        Point::new(10.0, 0.0), // 3
+        Point::new(5.0, 10.5), // 4, excluded, just over the top edge
+    ];
+    let inpoints = vec![0,1,2,3];
+    let expoints = vec![4];
+    let inblob = make_inblob(points.len(), &inpoints);
+    let hull = giftwrap(&points, &inpoints);
+
+    // Far enough away already.
+    let refined = refine_poly(&points, &inblob, hull.clone(), &inpoints, &expoints, 0.1);
+    assert_eq!(refined, hull);
+    // Too close, so it gets pulled in as a dent.
+    let refined = refine_poly(&points, &inblob, hull.clone(), &inpoints, &expoints, 1.0);
+    assert_eq!(refined, vec![1, 4, 2, 3, 0]);
+}
+#[test]
+fn test_find_hull_huge() {
+    use input::{read_points, read_combs};
+
+    // Every stage has to cope with a hull of over a thousand points.
+    let points = read_points("datasets/huge.dat").unwrap();
+    let combs = read_combs("datasets/huge.txt", points.len()).unwrap();
+    let nearest = compute_nearest_distances(&points);
+    let blob = find_hull(&Config::default(), &points, &combs[0][0], &nearest).unwrap();
+    assert_eq!(find_crossing(&points, &blob.hull), None);
+    for ix in (0..points.len()).filter(|&ix| !blob.hull.contains(&ix)) {
+        assert_eq!(point_inside(&points, &points[ix], &blob.hull), blob.inblob[ix]);
+    }
+}
+
+/// Untangles the polygon so that no two edges cross.
+/// Uses 2-opt swaps: when edges ab and cd cross, the path b..c is reversed,
+/// giving edges ac and bd instead.  That always shortens the polygon, so
+/// it can't loop forever.
+/// The result is clockwise, like the output of giftwrap.
+pub fn rm_crossing(points: &Vec<Point>, mut hull: Vec<Index>) -> Vec<Index> {
+    while let Some((i, j)) = find_crossing(points, &hull) {
+        debug!("Edges after {} and {} cross, swapping", hull[i], hull[j]);
+        hull[i+1..j+1].reverse();
+    }
+    if hull.len() >= 3 && !is_clockwise(points, &hull) {
+        hull.reverse();
+    }
     hull
 }
+#[test]
+fn test_rm_crossing_bowtie() {
+    let points = vec![
+        Point::new(0.0, 0.0), // 0
+        Point::new(0.0, 1.0), // 1
+        Point::new(1.0, 1.0), // 2
+        Point::new(1.0, 0.0), // 3
+    ];
+    let hull = rm_crossing(&points, vec![0, 2, 1, 3]);
+    assert!(find_crossing(&points, &hull).is_none());
+    assert!(is_clockwise(&points, &hull));
+    assert_eq!(hull.len(), 4);
+}
+#[test]
+fn test_rm_crossing_bad_comb() {
+    use input::{read_points, read_combs};
+
+    let config = Config::default();
+    let points = read_points("datasets/r100.dat").unwrap();
+    let combs = read_combs("datasets/bad_comb.txt", points.len()).unwrap();
+    let nearest = compute_nearest_distances(&points);
+    for inpoints in &combs[0] {
+        let blob = find_hull(&config, &points, inpoints, &nearest).unwrap();
+        assert_eq!(find_crossing(&points, &blob.hull), None);
+    }
+}
+#[test]
+fn test_find_hull_unknown_algorithm() {
+    let mut config = Config::default();
+    config.run.hull_algorithm = "bogosort".to_string();
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.), Point::new(0., 1.)];
+    let nearest = compute_nearest_distances(&points);
+    let problem = find_hull(&config, &points, &vec![0, 1, 2], &nearest).err().unwrap();
+    assert_eq!(problem.key, "run.hull_algorithm");
+}
+
+/// Finds a pair of crossing edges, returning the positions in the hull of
+/// their first vertices, in increasing order.
+fn find_crossing(points: &Vec<Point>, hull: &Vec<Index>) -> Option<(usize, usize)> {
+    let n = hull.len();
+    for i in 0..n {
+        // Skip neighbouring edges, they only share a vertex.
+        for j in i+2..n {
+            if i == 0 && j == n - 1 {
+                continue;
+            }
+            if segments_cross(points[hull[i]], points[hull[i+1]],
+                              points[hull[j]], points[hull[(j+1) % n]]) {
+                return Some((i, j));
+            }
+        }
+    }
+    None
+}
+
+/// Uses the shoelace formula, so this is only meaningful for simple polygons.
+fn is_clockwise(points: &Vec<Point>, hull: &Vec<Index>) -> bool {
+    let mut twice_area = 0.;
+    for hull_ix in 0..hull.len() {
+        let a = points[hull[hull_ix]];
+        let b = points[hull[(hull_ix + 1) % hull.len()]];
+        twice_area += a.x * b.y - b.x * a.y;
+    }
+    twice_area < 0.
+}
+
+/// Is a point that is not on the polygon on the correct side of it,
+/// and at least epsilon away from every edge?
+fn is_separated(points: &Vec<Point>, inblob: &Vec<bool>, hull: &Vec<Index>,
+                ix: Index, epsilon: f64) -> bool {
+    let p = points[ix];
+    let (distance, _) = nearest_edge(points, &p, hull);
+    distance >= epsilon && point_inside(points, &p, hull) == inblob[ix]
+}
+
+/// Finds the edge closest to p, returning the distance and the position of
+/// the edge's first vertex in the hull.
+fn nearest_edge(points: &Vec<Point>, p: &Point, hull: &Vec<Index>) -> (f64, usize) {
+    let mut best = (f64::INFINITY, 0);
+    for hull_ix in 0..hull.len() {
+        let a = points[hull[hull_ix]];
+        let b = points[hull[(hull_ix + 1) % hull.len()]];
+        let distance = segment_distance(p, &a, &b);
+        if distance < best.0 {
+            best = (distance, hull_ix);
+        }
+    }
+    best
+}
+
+/// Picks the edge to splice the point ix into, trying the nearest edges first.
+/// The two new edges must not cross the polygon, and the triangle they cut off
+/// or add must not move any other point to the wrong side.
+/// On_hull says which points are on the hull already.
+fn insertion_edge(points: &Vec<Point>, inblob: &Vec<bool>,
+                  hull: &Vec<Index>, on_hull: &Vec<bool>, ix: Index) -> Option<usize> {
+    let p = points[ix];
+    let mut edges: Vec<(f64, usize)> = (0..hull.len()).map(|hull_ix| {
+        let a = points[hull[hull_ix]];
+        let b = points[hull[(hull_ix + 1) % hull.len()]];
+        (segment_distance(&p, &a, &b), hull_ix)
+    }).collect();
+    edges.sort_by(|x, y| cmp_f64(x.0, y.0));
+
+    'edges: for &(_, hull_ix) in &edges {
+        let a_ix = hull[hull_ix];
+        let b_ix = hull[(hull_ix + 1) % hull.len()];
+        for other_ix in 0..hull.len() {
+            let c_ix = hull[other_ix];
+            let d_ix = hull[(other_ix + 1) % hull.len()];
+            for &(e0, e1) in &[(a_ix, ix), (ix, b_ix)] {
+                if e0 == c_ix || e0 == d_ix || e1 == c_ix || e1 == d_ix {
+                    continue;
+                }
+                if segments_cross(points[e0], points[e1], points[c_ix], points[d_ix]) {
+                    continue 'edges;
+                }
+            }
+        }
+        // If p is outside, the triangle gets added to the polygon,
+        // otherwise it gets cut out.
+        let triangle = vec![a_ix, ix, b_ix];
+        let adding = !point_inside(points, &p, hull);
+        for other in 0..points.len() {
+            if other != ix && !on_hull[other] && inblob[other] != adding
+                    && point_inside(points, &points[other], &triangle) {
+                continue 'edges;
+            }
+        }
+        return Some(hull_ix);
+    }
+    None
+}
 
-fn point_inside(points: &Vec<Point>, p: &Point, hull: &Vec<Index>) -> bool {
+/// Distance from p to the closest point on the segment ab.
+fn segment_distance(p: &Point, a: &Point, b: &Point) -> f64 {
+    use na::{Norm, dot};
+    let ab = *b - *a;
+    let ap = *p - *a;
+    let len2 = ab.sqnorm();
+    if len2 == 0. {
+        return ap.norm();
+    }
+    let t = (dot(&ab, &ap) / len2).max(0.).min(1.);
+    (*p - (*a + ab * t)).norm()
+}
+#[test]
+fn test_segment_distance() {
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(2.0, 0.0);
+    assert_eq!(segment_distance(&Point::new(1.0, 1.0), &a, &b), 1.0);
+    assert_eq!(segment_distance(&Point::new(-3.0, 4.0), &a, &b), 5.0);
+    assert_eq!(segment_distance(&Point::new(1.0, 0.0), &a, &b), 0.0);
+}
+
+/// Do the segments ab and cd cross at a single point interior to both?
+/// Segments that merely touch or overlap don't count.
+pub fn segments_cross(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let o1 = orientation(a, b, c);
+    let o2 = orientation(a, b, d);
+    let o3 = orientation(c, d, a);
+    let o4 = orientation(c, d, b);
+    o1 != Colinear && o2 != Colinear && o3 != Colinear && o4 != Colinear
+        && o1 != o2 && o3 != o4
+}
+#[test]
+fn test_segments_cross() {
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(1.0, 1.0);
+    let c = Point::new(0.0, 1.0);
+    let d = Point::new(1.0, 0.0);
+    assert!(segments_cross(a, b, c, d));
+    assert!(!segments_cross(a, c, b, d));
+    assert!(!segments_cross(a, b, b, d)); // Touching at b
+}
+
+/// Whether p is strictly inside the polygon going through points
+/// in the order of hull.
+pub fn point_inside(points: &Vec<Point>, p: &Point, hull: &Vec<Index>) -> bool {
     let mut inside = false;
     let mut e0 = points[hull[hull.len() - 1]];
     let mut y0 = (e0.y > p.y);
@@ -280,10 +933,11 @@ fn point_inside(points: &Vec<Point>, p: &Point, hull: &Vec<Index>) -> bool {
         let y1 = (e1.y > p.y);
         if y0 != y1 {
             // Mybe re-read http://www.ecse.rpi.edu/~wrf/Research/Short_Notes/pnpoly.html
-            // t == y1 is more efficient maybe, or we can just use t2 instead
-            // as it it more obviously correct.  Should benchmark it.
-            // bool t = ((e1.y - p.y) * (e0.x - e1.x) >= (e1.x - p.x) * (e0.y - e1.y));
-            let t2 = (p.x < ((e1.x-e0.x) * (p.y-e0.y) / (e1.y-e0.y)) + e0.x);
+            // p is left of the crossing exactly when it's on the left of
+            // an upwards edge, or the right of a downwards one.
+            // Points on the edge don't count.
+            let det = orient2d(e0, e1, *p);
+            let t2 = if y1 { det > 0. } else { det < 0. };
             if t2 {
                 inside = !inside;
             }
@@ -314,10 +968,3 @@ fn test_point_inside() {
     }
 
 }
-
-fn in_hull(a : Index, p: &Vec<Index>) -> bool {
-    match p.iter().position(|x| *x == a) {
-        Some(_) => true,
-        None    => false,
-    }
-}
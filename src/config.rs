@@ -3,41 +3,81 @@ extern crate docopt;
 extern crate toml;
 
 
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
+use hull;
+use hull::ConvexHull;
+use rustc_serialize::Decodable;
 use self::docopt::Docopt;
+use self::toml::{Parser, Value};
 
 // Write the Docopt usage string.
 static USAGE: &'static str = "
-Usage: ./draw [-d] <points-file> <combs-file> <dest-prefix>
+Usage: ./draw [-d] [-c] [--json] [-g] [-m] [-j <n>] [--config <path>] [--set <setting>]...
+              <points-file> <combs-file> <dest-prefix>
+       ./draw verify [--config <path>] [--set <setting>]... <points-file> <combs-file>
+
+verify checks every blob has its set's points inside and the rest outside,
+and an outline that doesn't cross itself, then prints a JSON report.
+It exits with 1 if any blob is wrong.
+
+Points and combs files ending in .json are read as JSON.
+Points files ending in .csv are read as CSV, with the columns named in the config.
 
 Options:
     -d, --output-directories  Output a forlder for each comb.
+    -c, --combine-sets        Draw all the sets of a comb in one image.
+    --json                    Also write the computed blobs to <dest-prefix>.json.
+    -g, --geojson             Also write the blob outlines to <dest-prefix>.geojson.
+    -m, --measures            Print the area, perimeter and centroid of every blob,
+                              and of the convex hull of its points.
+    -j <n>, --jobs <n>        How many images to work on at once [default: 1].
+    --config <path>           The config file to use [default: config.toml].
+    --set <setting>           Change a setting from the config file,
+                              like img.width=2000 or draw.labels=false.
 ";
 
 #[derive(RustcDecodable, Debug)]
 pub struct Args {
+    pub cmd_verify: bool,
     pub arg_points_file: String,
     pub arg_combs_file: String,
     pub arg_dest_prefix: String,
     pub flag_output_directories: bool,
+    pub flag_combine_sets: bool,
+    pub flag_json: bool,
+    pub flag_geojson: bool,
+    pub flag_measures: bool,
+    pub flag_jobs: usize,
+    pub flag_config: String,
+    pub flag_set: Vec<String>,
 }
 
 
-#[derive(RustcDecodable, Debug)]
+#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug)]
 pub struct ConfigRun {
     pub fix_hull: bool,
     pub refine_poly: bool,
     pub rm_crossing: bool,
+    pub hull_algorithm: String,
 }
-#[derive(RustcDecodable, Debug)]
+#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug)]
+pub struct ConfigInput {
+    pub id_column: String,
+    pub x_column: String,
+    pub y_column: String,
+    pub label_column: String,
+}
+#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug)]
 pub struct ConfigImg {
     pub width : i32,
     pub height: i32,
     pub edge_buffer: f64,
+    pub format: String,
 }
 
-#[derive(RustcDecodable, Debug)]
+#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug)]
 pub struct ConfigDraw {
     pub axis: bool,
     pub points: bool,
@@ -51,27 +91,383 @@ pub struct ConfigDraw {
     pub axis_thickness: f64,
 }
 
-#[derive(RustcDecodable, Debug)]
+#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug)]
 pub struct ConfigB2 {
     pub mindist_radius_factor: f64,
     pub refine_epsilon: f64,
 }
 
-#[derive(RustcDecodable, Debug)]
+#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug)]
 pub struct Config {
     pub run:  ConfigRun,
+    pub input: ConfigInput,
     pub img:  ConfigImg,
     pub draw: ConfigDraw,
     pub b2:   ConfigB2
 }
 
+/// The settings config.toml ships with.
+/// Anything a config file leaves out is taken from here.
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            run: ConfigRun {
+                fix_hull: true,
+                refine_poly: true,
+                rm_crossing: true,
+                hull_algorithm: "giftwrap".to_string(),
+            },
+            input: ConfigInput {
+                id_column: "id".to_string(),
+                x_column: "x".to_string(),
+                y_column: "y".to_string(),
+                label_column: "label".to_string(),
+            },
+            img: ConfigImg {
+                width: 1000,
+                height: 1000,
+                edge_buffer: 1.5,
+                format: "png".to_string(),
+            },
+            draw: ConfigDraw {
+                axis: true,
+                points: true,
+                polygon: true,
+                blob: true,
+                background: true,
+                labels: true,
+                fontsize: 20.0,
+                point_radius: 3.0,
+                polygon_thickness: 1.6,
+                axis_thickness: 0.6,
+            },
+            b2: ConfigB2 {
+                mindist_radius_factor: 3.0,
+                refine_epsilon: 1.0,
+            },
+        }
+    }
+}
+
+
+/// A setting whose value we can't work with.
+#[derive(PartialEq, Debug)]
+pub struct ConfigProblem {
+    /// Which setting, like `img.width`.
+    pub key: &'static str,
+    /// Another setting that's just as much to blame, like the other side of a clash.
+    pub other_key: Option<&'static str>,
+    pub problem: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.problem)
+    }
+}
+
+impl Config {
+    /// The convex hull algorithm named by `run.hull_algorithm`.
+    pub fn hull_algorithm(&self) -> Result<Box<ConvexHull>, ConfigProblem> {
+        hull::from_name(&self.run.hull_algorithm).ok_or_else(|| ConfigProblem{
+            key: "run.hull_algorithm",
+            other_key: None,
+            problem: format!("\"{}\" isn't one of giftwrap, monotone_chain or quickhull",
+                             self.run.hull_algorithm),
+        })
+    }
+
+    /// Checks every setting, returning all the problems found.
+    pub fn validate(&self) -> Result<(), Vec<ConfigProblem>> {
+        let mut problems = vec![];
+        if let Err(problem) = self.hull_algorithm() {
+            problems.push(problem);
+        }
+        {
+            let mut check = |ok: bool, key: &'static str, problem: String| {
+                if !ok {
+                    problems.push(ConfigProblem{ key: key, other_key: None, problem: problem });
+                }
+            };
+
+            check(!self.input.x_column.is_empty(), "input.x_column",
+                  "the column name can't be empty".to_string());
+            check(!self.input.y_column.is_empty(), "input.y_column",
+                  "the column name can't be empty".to_string());
+
+            check(self.img.width > 0, "img.width",
+                  format!("must be positive, not {}", self.img.width));
+            check(self.img.height > 0, "img.height",
+                  format!("must be positive, not {}", self.img.height));
+            check(self.img.edge_buffer >= 1., "img.edge_buffer",
+                  format!("must be at least 1, or points fall off the image, not {}",
+                          self.img.edge_buffer));
+            check(["png", "svg", "eps", "ps", "pdf"].contains(&&self.img.format[..]), "img.format",
+                  format!("\"{}\" isn't one of png, svg, eps, ps or pdf", self.img.format));
+
+            check(self.draw.fontsize > 0., "draw.fontsize",
+                  format!("must be positive, not {}", self.draw.fontsize));
+            check(self.draw.point_radius >= 0., "draw.point_radius",
+                  format!("can't be negative, not {}", self.draw.point_radius));
+            check(self.draw.polygon_thickness >= 0., "draw.polygon_thickness",
+                  format!("can't be negative, not {}", self.draw.polygon_thickness));
+            check(self.draw.axis_thickness >= 0., "draw.axis_thickness",
+                  format!("can't be negative, not {}", self.draw.axis_thickness));
+
+            check(self.b2.mindist_radius_factor > 2., "b2.mindist_radius_factor",
+                  format!("must be more than 2, or neighbouring discs overlap, not {}",
+                          self.b2.mindist_radius_factor));
+            check(self.b2.refine_epsilon >= 0., "b2.refine_epsilon",
+                  format!("can't be negative, not {}", self.b2.refine_epsilon));
+        }
+        if self.input.x_column == self.input.y_column {
+            problems.push(ConfigProblem{
+                key: "input.y_column",
+                other_key: Some("input.x_column"),
+                problem: format!("x and y can't both be in column \"{}\"", self.input.y_column),
+            });
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+#[test]
+fn test_validate() {
+    assert_eq!(Config::default().validate(), Ok(()));
+
+    let mut config = Config::default();
+    config.img.width = 0;
+    config.img.edge_buffer = 0.5;
+    config.b2.mindist_radius_factor = 1.5;
+    config.img.format = "gif".to_string();
+    let problems = config.validate().unwrap_err();
+    let keys: Vec<&str> = problems.iter().map(|p| p.key).collect();
+    assert_eq!(keys, vec!["img.width", "img.edge_buffer", "img.format",
+                          "b2.mindist_radius_factor"]);
+    assert_eq!(problems[0].to_string(), "img.width: must be positive, not 0");
+}
 
 // TODO(tbelaire) better error handling
-pub fn parse_config( mut f: File ) -> Config {
+pub fn parse_config<R: Read>( mut f: R ) -> Config {
     let mut contents: String = String::new();
     f.read_to_string(&mut contents).unwrap();
 
-    toml::decode_str(&contents).expect("Failed to parse config file")
+    config_from_str("config file", &contents, &vec![])
+        .unwrap_or_else(|e| panic!("Failed to parse config file: {}", e))
+}
+
+/// Loads the config file at path, then applies each of overrides on top,
+/// which look like `section.key=value`.
+/// Errors are messages to show the user.
+pub fn load_config(path: &str, overrides: &Vec<String>) -> Result<Config, String> {
+    let contents = try!(read_file(path));
+    config_from_str(path, &contents, overrides)
+}
+
+/// Loads the config like load_config, then validates it.
+/// Each problem is put down to where its value came from:
+/// the --set that changed it, the config file, or the defaults.
+/// A problem with two settings is put down to the one that was changed.
+pub fn load_valid_config(path: &str, overrides: &Vec<String>) -> Result<Config, String> {
+    let contents = try!(read_file(path));
+    valid_config_from_str(path, &contents, overrides)
+}
+
+/// Reads the config like config_from_str, then validates it.
+/// Name is what to call contents in error messages.
+pub fn valid_config_from_str(name: &str, contents: &str, overrides: &Vec<String>)
+        -> Result<Config, String> {
+    let config = try!(config_from_str(name, contents, overrides));
+    let problems = match config.validate() {
+        Ok(()) => return Ok(config),
+        Err(problems) => problems,
+    };
+    let file = Parser::new(contents).parse().unwrap_or_else(Default::default);
+    let messages: Vec<String> = problems.iter().map(|problem| {
+        let keys: Vec<&str> = Some(problem.key).into_iter().chain(problem.other_key).collect();
+        let set = overrides.iter().rev().filter_map(|setting| {
+            let key = setting.split('=').next().unwrap().trim();
+            keys.iter().find(|&&k| k == key).map(|&k| (k, setting))
+        }).next();
+        let (key, source) = match set {
+            Some((key, setting)) => (key, format!("--set {}", setting)),
+            None => match keys.iter().find(|&&key| has_setting(&file, key)) {
+                Some(&key) => (key, name.to_string()),
+                None => (problem.key, "default".to_string()),
+            },
+        };
+        format!("{}: {}: {}", source, key, problem.problem)
+    }).collect();
+    Err(messages.connect("\n"))
+}
+#[test]
+fn test_valid_config_sources() {
+    let contents = "[img]\nwidth = 0\nformat = \"gif\"\n[input]\nx_column = \"y\"\n";
+    assert!(valid_config_from_str("fine", "", &vec![]).is_ok());
+    let err = valid_config_from_str("my.toml", contents,
+                                    &vec!["img.format=svg".to_string(),
+                                          "img.height=-1".to_string()]).unwrap_err();
+    assert_eq!(err, "my.toml: img.width: must be positive, not 0\n\
+                     --set img.height=-1: img.height: must be positive, not -1\n\
+                     my.toml: input.x_column: x and y can't both be in column \"y\"");
+
+    let err = valid_config_from_str("my.toml", "", &vec!["img.height = -1".to_string(),
+                                                         "input.x_column = y".to_string()])
+        .unwrap_err();
+    assert_eq!(err, "--set img.height = -1: img.height: must be positive, not -1\n\
+                     --set input.x_column = y: input.x_column: \
+                     x and y can't both be in column \"y\"");
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    let mut contents = String::new();
+    match File::open(path).and_then(|mut f| f.read_to_string(&mut contents)) {
+        Ok(_) => Ok(contents),
+        Err(e) => Err(format!("{}: {}", path, e)),
+    }
+}
+
+/// Whether table sets key, which looks like `section.key`.
+fn has_setting(table: &toml::Table, key: &str) -> bool {
+    let names: Vec<&str> = key.split('.').collect();
+    match table.get(names[0]) {
+        Some(&Value::Table(ref section)) => names.len() == 2 && section.contains_key(names[1]),
+        _ => false,
+    }
+}
+
+/// Reads the config in contents on top of the defaults, then applies overrides.
+/// Name is what to call contents in error messages.
+pub fn config_from_str(name: &str, contents: &str, overrides: &Vec<String>)
+        -> Result<Config, String> {
+    let mut parser = Parser::new(contents);
+    let file = match parser.parse() {
+        Some(table) => table,
+        None => {
+            let messages: Vec<String> = parser.errors.iter().map(|e| {
+                let (line, column) = parser.to_linecol(e.lo);
+                format!("{}:{}:{}: {}", name, line + 1, column + 1, e.desc)
+            }).collect();
+            return Err(messages.connect("\n"));
+        },
+    };
+
+    let mut table = match toml::encode(&Config::default()) {
+        Value::Table(table) => table,
+        _ => unreachable!(),
+    };
+    merge(&mut table, file, name);
+    for setting in overrides {
+        try!(apply_override(&mut table, setting));
+    }
+
+    let mut decoder = toml::Decoder::new(Value::Table(table));
+    Config::decode(&mut decoder).map_err(|e| format!("{}: {}", name, e))
+}
+
+/// Puts each setting in file in place of the default one.
+/// Settings we don't know about are left out, with a warning.
+fn merge(defaults: &mut toml::Table, file: toml::Table, name: &str) {
+    for (section, settings) in file {
+        let known = match defaults.get_mut(&section) {
+            Some(&mut Value::Table(ref mut known)) => known,
+            _ => {
+                warn!("{}: ignoring unknown section {}", name, section);
+                continue;
+            },
+        };
+        let settings = match settings {
+            Value::Table(settings) => settings,
+            _ => {
+                warn!("{}: ignoring {}, which should be a section", name, section);
+                continue;
+            },
+        };
+        for (key, value) in settings {
+            if known.contains_key(&key) {
+                known.insert(key, value);
+            } else {
+                warn!("{}: ignoring unknown setting {}.{}", name, section, key);
+            }
+        }
+    }
+}
+#[test]
+fn test_config_defaults() {
+    assert_eq!(load_config("config.toml", &vec![]).unwrap(), Config::default());
+
+    let config = config_from_str("partial", "[img]\nwidth = 300\nheight = 200\n", &vec![])
+        .unwrap();
+    assert_eq!(config.img.width, 300);
+    assert_eq!(config.img.height, 200);
+    assert_eq!(config.img.format, "png");
+    assert_eq!(config.draw, Config::default().draw);
+
+    let err = config_from_str("bad", "[img]\nwidth = \"wide\"\n", &vec![]).unwrap_err();
+    assert!(err.starts_with("bad: expected a value of type `integer`"), "{}", err);
+}
+
+/// Sets one value in the config, from a setting like `img.width=2000`.
+/// The value is read as TOML, or as a string if it isn't valid TOML,
+/// so `img.format=svg` doesn't need quotes.
+fn apply_override(table: &mut toml::Table, setting: &str) -> Result<(), String> {
+    let bad = || format!("--set {}: expected a setting like section.key=value", setting);
+    let equals = try!(setting.find('=').ok_or_else(&bad));
+    let (path, value) = (setting[..equals].trim(), setting[equals + 1..].trim());
+    let names: Vec<&str> = path.split('.').collect();
+    if names.len() != 2 {
+        return Err(bad());
+    }
+    let (section, key) = (names[0], names[1]);
+
+    let mut value = match Parser::new(&format!("value = {}", value)).parse() {
+        Some(mut parsed) => parsed.remove("value").unwrap(),
+        None => Value::String(value.to_string()),
+    };
+
+    let old = match table.get_mut(section).and_then(|s| match *s {
+        Value::Table(ref mut t) => t.get_mut(key),
+        _ => None,
+    }) {
+        Some(old) => old,
+        None => return Err(format!("--set {}: there is no setting {}", setting, path)),
+    };
+    // Whole numbers are fine for settings that take fractions.
+    let whole = match (&*old, &value) {
+        (&Value::Float(_), &Value::Integer(i)) => Some(i as f64),
+        _ => None,
+    };
+    if let Some(x) = whole {
+        value = Value::Float(x);
+    }
+    if !old.same_type(&value) {
+        return Err(format!("--set {}: expected type {} for {}, found {}",
+                           setting, old.type_str(), path, value.type_str()));
+    }
+    *old = value;
+    Ok(())
+}
+#[test]
+fn test_load_config_overrides() {
+    let overrides = vec!["img.width=2000".to_string(),
+                         "draw.labels=false".to_string(),
+                         "img.format=svg".to_string(),
+                         "b2.refine_epsilon=2".to_string()];
+    let config = load_config("config.toml", &overrides).unwrap();
+    assert_eq!(config.img.width, 2000);
+    assert_eq!(config.draw.labels, false);
+    assert_eq!(config.img.format, "svg");
+    assert_eq!(config.b2.refine_epsilon, 2.0);
+
+    let err = load_config("config.toml", &vec!["img.depth=3".to_string()]).unwrap_err();
+    assert_eq!(err, "--set img.depth=3: there is no setting img.depth");
+    let err = load_config("config.toml", &vec!["img.width=wide".to_string()]).unwrap_err();
+    assert_eq!(err, "--set img.width=wide: expected type integer for img.width, found string");
+    let err = load_config("config.toml", &vec!["labels".to_string()]).unwrap_err();
+    assert_eq!(err, "--set labels: expected a setting like section.key=value");
 }
 
 pub fn parse_args() -> Args {
@@ -79,5 +475,14 @@ pub fn parse_args() -> Args {
         .and_then(|d| d.decode())
         .unwrap_or_else(|e| e.exit())
 }
+#[test]
+fn test_parse_jobs() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.argv(vec!["draw", "-j", "4", "p", "c", "out"].into_iter()).decode())
+        .unwrap();
+    assert_eq!(args.flag_jobs, 4);
+    assert!(!args.flag_json);
+    assert_eq!(args.arg_dest_prefix, "out");
+}
 
 
@@ -0,0 +1,165 @@
+//! Reading points from CSV, where each point can have an id and a label
+//! as well as its coordinates.
+
+use std::collections::HashSet;
+
+use config::ConfigInput;
+use input::{LabelledPoints, ParseError, parse_coordinate};
+use types::Point;
+
+/// A field of a CSV line, and the column it started at.
+struct Field {
+    text: String,
+    column: usize,
+}
+
+/// Splits a line of CSV into its fields.
+/// Fields can be quoted, with "" for a quote inside them,
+/// but they can't run over more than one line.
+fn split_line(file: &str, line_num: usize, line: &str) -> Result<Vec<Field>, ParseError> {
+    let mut fields = vec![];
+    let mut field = Field{ text: String::new(), column: 1 };
+    let mut in_quotes = false;
+    let mut was_quoted = false;
+
+    let mut chars = line.chars().enumerate().peekable();
+    while let Some((i, c)) = chars.next() {
+        if in_quotes {
+            if c != '"' {
+                field.text.push(c);
+            } else if chars.peek().map(|&(_, next)| next) == Some('"') {
+                chars.next();
+                field.text.push('"');
+            } else {
+                in_quotes = false;
+            }
+            continue;
+        }
+        match c {
+            ',' => {
+                if !was_quoted {
+                    field.text = field.text.trim().to_string();
+                }
+                fields.push(field);
+                field = Field{ text: String::new(), column: i + 2 };
+                was_quoted = false;
+            },
+            '"' if field.text.trim().is_empty() && !was_quoted => {
+                field.text.clear();
+                in_quotes = true;
+                was_quoted = true;
+            },
+            // Spaces between a closing quote and the comma don't count.
+            _ if was_quoted && c.is_whitespace() => (),
+            _ => field.text.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(ParseError::Syntax{
+            file: file.to_string(), line: line_num, column: line.chars().count() + 1,
+            expected: "a closing quote".to_string(), found: "the end of the line".to_string(),
+        });
+    }
+    if !was_quoted {
+        field.text = field.text.trim().to_string();
+    }
+    fields.push(field);
+    Ok(fields)
+}
+#[test]
+fn test_split_line() {
+    let fields = split_line("f", 1, "a, 1.5 ,\"Say \"\"hi\"\", bye\" \r").unwrap();
+    let found: Vec<(&str, usize)> = fields.iter().map(|f| (&f.text[..], f.column)).collect();
+    assert_eq!(found, vec![("a", 1), ("1.5", 3), ("Say \"hi\", bye", 9)]);
+
+    let err = split_line("f", 4, "a,\"b").unwrap_err();
+    assert_eq!(err.to_string(), "f:4:5: expected a closing quote, found the end of the line");
+}
+
+/// Reads points from CSV with a header line naming the columns.
+/// The x and y columns have to be there, the id and label ones are optional,
+/// and any others are ignored.
+pub fn parse_points(file: &str, contents: &str, columns: &ConfigInput)
+        -> Result<LabelledPoints, ParseError> {
+    let mut lines = contents.lines().enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|&(_, line)| !line.trim().is_empty());
+
+    let (header_num, header) = match lines.next() {
+        Some((line_num, line)) => (line_num, try!(split_line(file, line_num, line))),
+        None => return Err(ParseError::Syntax{
+            file: file.to_string(), line: 1, column: 1,
+            expected: "a header line".to_string(), found: "end of file".to_string(),
+        }),
+    };
+    let find = |name: &str| header.iter().position(|field| field.text == name);
+    let mut required = vec![];
+    for name in &[&columns.x_column, &columns.y_column] {
+        match find(name) {
+            Some(column) => required.push(column),
+            None => return Err(ParseError::Syntax{
+                file: file.to_string(), line: header_num, column: 1,
+                expected: format!("a \"{}\" column", name),
+                found: format!("columns {}", header.iter()
+                               .map(|field| format!("\"{}\"", field.text))
+                               .collect::<Vec<String>>().connect(", ")),
+            }),
+        }
+    }
+    let (x_column, y_column) = (required[0], required[1]);
+    let id_column = find(&columns.id_column);
+    let label_column = find(&columns.label_column);
+
+    let mut result = LabelledPoints::unlabelled(vec![]);
+    let mut seen_ids = HashSet::new();
+    for (line_num, line) in lines {
+        let fields = try!(split_line(file, line_num, line));
+        if fields.len() != header.len() {
+            return Err(ParseError::Syntax{
+                file: file.to_string(), line: line_num, column: 1,
+                expected: format!("{} fields", header.len()),
+                found: format!("{} fields", fields.len()),
+            });
+        }
+        let (x, y) = (&fields[x_column], &fields[y_column]);
+        let x = try!(parse_coordinate(file, line_num, x.column, &x.text, "an x coordinate"));
+        let y = try!(parse_coordinate(file, line_num, y.column, &y.text, "a y coordinate"));
+        result.points.push(Point::new(x, y));
+
+        if let Some(column) = id_column {
+            let field = &fields[column];
+            if !seen_ids.insert(field.text.clone()) {
+                return Err(ParseError::Syntax{
+                    file: file.to_string(), line: line_num, column: field.column,
+                    expected: "a new id".to_string(),
+                    found: format!("\"{}\" again", field.text),
+                });
+            }
+            result.ids.push(field.text.clone());
+        }
+        if let Some(column) = label_column {
+            result.labels.push(fields[column].text.clone());
+        }
+    }
+    Ok(result)
+}
+#[test]
+fn test_parse_points() {
+    use config::Config;
+    let config = Config::default();
+
+    let csv = "y,x,label\n2,1,one\n\n4,3,\"three, four\"\n";
+    let points = parse_points("f", csv, &config.input).unwrap();
+    assert_eq!(points.points, vec![Point::new(1., 2.), Point::new(3., 4.)]);
+    assert_eq!(points.labels, vec!["one", "three, four"]);
+    assert!(points.ids.is_empty());
+
+    let err = parse_points("f", "id,x\n", &config.input).unwrap_err();
+    assert_eq!(err.to_string(), "f:1:1: expected a \"y\" column, found columns \"id\", \"x\"");
+    let err = parse_points("f", "id,x,y\na,1,2\na,3,4\n", &config.input).unwrap_err();
+    assert_eq!(err.to_string(), "f:3:1: expected a new id, found \"a\" again");
+    let err = parse_points("f", "x,y\n1,2,3\n", &config.input).unwrap_err();
+    assert_eq!(err.to_string(), "f:2:1: expected 2 fields, found 3 fields");
+    let err = parse_points("f", "x,y\n1,NaN\n", &config.input).unwrap_err();
+    assert_eq!(err.to_string(), "f:2:3: expected a y coordinate, found \"NaN\"");
+}
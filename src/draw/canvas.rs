@@ -0,0 +1,164 @@
+use draw::cairo::Cairo;
+use draw::cairo::font::slant::Slant;
+use draw::cairo::font::weight::Weight;
+
+/// The drawing operations our renderers need.
+/// These mirror cairo's, down to how arcs join the current path,
+/// so every backend traces the same shapes.
+pub trait Canvas {
+    fn scale(&mut self, sx: f64, sy: f64);
+    fn translate(&mut self, tx: f64, ty: f64);
+
+    fn set_source_rgba(&mut self, r: f64, g: f64, b: f64, a: f64);
+    fn set_line_width(&mut self, width: f64);
+    fn select_font(&mut self, family: &str, bold: bool, size: f64);
+
+    /// Fills the whole surface with the current colour.
+    fn paint(&mut self);
+
+    fn new_path(&mut self);
+    fn move_to(&mut self, x: f64, y: f64);
+    fn line_to(&mut self, x: f64, y: f64);
+    /// Adds an arc with increasing angle, joined to the current point by a line.
+    fn arc(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, angle2: f64);
+    /// Adds an arc with decreasing angle, joined to the current point by a line.
+    fn arc_negative(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, angle2: f64);
+    fn close_path(&mut self);
+
+    fn stroke(&mut self);
+    fn fill(&mut self);
+    fn fill_preserve(&mut self);
+    /// Draws text at the current point.
+    fn show_text(&mut self, text: &str);
+}
+
+impl Canvas for Cairo {
+    fn scale(&mut self, sx: f64, sy: f64) {
+        Cairo::scale(self, sx, sy);
+    }
+    fn translate(&mut self, tx: f64, ty: f64) {
+        Cairo::translate(self, tx, ty);
+    }
+
+    fn set_source_rgba(&mut self, r: f64, g: f64, b: f64, a: f64) {
+        Cairo::set_source_rgba(self, r, g, b, a);
+    }
+    fn set_line_width(&mut self, width: f64) {
+        Cairo::set_line_width(self, width);
+    }
+    fn select_font(&mut self, family: &str, bold: bool, size: f64) {
+        let weight = if bold { Weight::Bold } else { Weight::Normal };
+        Cairo::select_font_face(self, family, Slant::Normal, weight);
+        Cairo::set_font_size(self, size);
+    }
+
+    fn paint(&mut self) {
+        Cairo::paint(self);
+    }
+
+    fn new_path(&mut self) {
+        Cairo::new_path(self);
+    }
+    fn move_to(&mut self, x: f64, y: f64) {
+        Cairo::move_to(self, x, y);
+    }
+    fn line_to(&mut self, x: f64, y: f64) {
+        Cairo::line_to(self, x, y);
+    }
+    fn arc(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, angle2: f64) {
+        Cairo::arc(self, xc, yc, radius, angle1, angle2);
+    }
+    fn arc_negative(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, angle2: f64) {
+        Cairo::arc_negative(self, xc, yc, radius, angle1, angle2);
+    }
+    fn close_path(&mut self) {
+        Cairo::close_path(self);
+    }
+
+    fn stroke(&mut self) {
+        Cairo::stroke(self);
+    }
+    fn fill(&mut self) {
+        Cairo::fill(self);
+    }
+    fn fill_preserve(&mut self) {
+        Cairo::fill_preserve(self);
+    }
+    fn show_text(&mut self, text: &str) {
+        Cairo::show_text(self, text);
+    }
+}
+
+/// What cairo keeps track of for us, and our own backends have to track themselves:
+/// the user to device transform and the current point.
+pub struct CanvasState {
+    width: i32,
+    height: i32,
+
+    // The transform, cairo style: (x, y) goes to (xx * x + x0, yy * y + y0).
+    pub xx: f64,
+    pub yy: f64,
+    pub x0: f64,
+    pub y0: f64,
+
+    /// Where the path ends, if there is one.
+    pub current: Option<(f64, f64)>,
+}
+
+impl CanvasState {
+    pub fn new(width: i32, height: i32) -> CanvasState {
+        CanvasState {
+            width: width,
+            height: height,
+            xx: 1., yy: 1., x0: 0., y0: 0.,
+            current: None,
+        }
+    }
+
+    pub fn scale(&mut self, sx: f64, sy: f64) {
+        self.xx = self.xx * sx;
+        self.yy = self.yy * sy;
+    }
+    pub fn translate(&mut self, tx: f64, ty: f64) {
+        self.x0 = self.x0 + self.xx * tx;
+        self.y0 = self.y0 + self.yy * ty;
+    }
+
+    /// The whole surface in user coordinates, as x, y, width and height,
+    /// for painting it whatever the current transform is.
+    pub fn surface_rect(&self) -> (f64, f64, f64, f64) {
+        // Subtracting from 0 so that no offset gives 0, not -0.
+        ((0. - self.x0) / self.xx, (0. - self.y0) / self.yy,
+         self.width as f64 / self.xx, self.height as f64 / self.yy)
+    }
+
+    pub fn new_path(&mut self) {
+        self.current = None;
+    }
+    /// Moves to (x, y), returning whether the path joins it with a line.
+    /// Like cairo, a line with nowhere to start from is just a move,
+    /// and so is the line to the start of an arc.
+    pub fn line_to(&mut self, x: f64, y: f64) -> bool {
+        let joined = self.current.is_some();
+        self.current = Some((x, y));
+        joined
+    }
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.current = Some((x, y));
+    }
+}
+
+#[test]
+fn test_canvas_state() {
+    let mut state = CanvasState::new(100, 50);
+    state.scale(2., 5.);
+    state.translate(-10., 1.);
+    assert_eq!((state.x0, state.y0), (-20., 5.));
+    assert_eq!(state.surface_rect(), (10., -1., 50., 10.));
+
+    assert!(!state.line_to(1., 2.));
+    assert!(state.line_to(3., 4.));
+    assert_eq!(state.current, Some((3., 4.)));
+    state.new_path();
+    assert_eq!(state.current, None);
+}
@@ -3,47 +3,214 @@ extern crate cairo;
 use draw::cairo::surface::Surface;
 use draw::cairo::Cairo;
 
-// Our helper functions live a in util.
-mod util;
-use self::util::*;
+// Everything is drawn onto a Canvas, so we can render to more than PNGs.
+pub mod canvas;
+pub mod pdf;
+mod png;
+pub mod ps;
+pub mod svg;
+use self::canvas::Canvas;
+use self::pdf::{PdfDocument, PdfCanvas};
+use self::ps::PsCanvas;
+use self::svg::SvgCanvas;
 
 // For all the parameters, we look to Config.
 use config::Config;
 
 // We need our types.
-use types::{Point, Vector, Index, Color, Radius};
+use blob::Blob;
+use geometry;
+use geometry::{Segment, Direction};
+use types::{Point, Index, Color};
 
 use std::f64;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
 use std::path::Path;
 
 use tau::TAU;
 
 
 /// This file deals with all the direct calls to cairo things.
-/// Helper utility functions will be in draw::util.
 
+/// A single blob to draw, in its own colour.
+/// Several of these drawn together make a picture of a whole comb.
+pub struct Layer<'a> {
+    pub blob: &'a Blob<'a>,
+    pub color: Color,
+}
+
+// Points in any of the blobs, and the ones left out of all of them.
+static INPOINTS_COLOR: &'static str = "ff3333";
+static EXPOINTS_COLOR: &'static str = "0033bb";
+
+// Colours for the layers of a comb, picked to stay apart when they overlap.
+static LAYER_COLORS: [&'static str; 8] = [
+    "aaaa00", "33a02c", "e31a1c", "1f78b4",
+    "ff7f00", "6a3d9a", "b15928", "a6cee3",
+];
+
+/// The colour to use for the i-th layer.
+/// The first one is the colour single blobs have always been drawn in.
+pub fn layer_color(i: usize) -> Color {
+    Color::from_hex(LAYER_COLORS[i % LAYER_COLORS.len()])
+}
+
+/// Draws the blobs in layers to path, one on top of the other.
+/// Points are labelled with labels, if there are any.
+/// Files ending in .svg, .eps, .ps or .pdf get vector output,
+/// anything else is a PNG.
 pub fn draw(config: &Config,
             points: &Vec<Point>,
-            hull: &Vec<Index>,
-            hull_color: &Color,
-            inpoints: &Vec<Index>,
-            inpoints_color: &Color,
-            expoints: &Vec<Index>,
-            expoints_color: &Color,
-            inblob: &Vec<bool>,
-            radii: &Vec<Radius>,
+            labels: &Vec<String>,
+            layers: &Vec<Layer>,
             path: &Path,
-            ) {
+            ) -> io::Result<()> {
+    let format = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(format @ "svg") | Some(format @ "eps") | Some(format @ "ps") |
+        Some(format @ "pdf") => format,
+        _ => "png",
+    };
+    let mut f = try!(File::create(path));
+    write_image(config, points, labels, layers, format, &mut f)
+}
+
+/// Draws the blobs in layers as a format image, and writes it to w.
+/// Format is png, svg, eps, ps or pdf.
+pub fn write_image<W: Write>(config: &Config,
+                             points: &Vec<Point>,
+                             labels: &Vec<String>,
+                             layers: &Vec<Layer>,
+                             format: &str,
+                             w: &mut W,
+                             ) -> io::Result<()> {
+    match format {
+        "svg" => {
+            let mut svg = SvgCanvas::new(config.img.width, config.img.height);
+            render(&mut svg, config, points, labels, layers);
+            svg.write_to(w)
+        },
+        "eps" | "ps" => {
+            let mut ps = PsCanvas::new(config.img.width, config.img.height);
+            render(&mut ps, config, points, labels, layers);
+            ps.write_to(w)
+        },
+        "pdf" => {
+            let mut doc = PdfDocument::new(config.img.width, config.img.height);
+            draw_page(&mut doc, config, points, labels, layers);
+            doc.write_to(w)
+        },
+        "png" => {
+            // Cairo only writes PNGs to files, so we encode its pixels ourselves.
+            let mut surface = render_png(config, points, labels, layers);
+            surface.flush();
+            let stride = surface.get_stride() as usize;
+            png::write_png(w, config.img.width as usize, config.img.height as usize, stride,
+                           surface.get_data())
+        },
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                format!("\"{}\" isn't one of png, svg, eps, ps or pdf", format))),
+    }
+}
+
+/// Draws just blob to path, labelling points with labels if there are any.
+pub fn draw_blob(config: &Config, blob: &Blob, labels: &Vec<String>, path: &Path)
+        -> io::Result<()> {
+    let layers = vec![Layer{ blob: blob, color: layer_color(0) }];
+    draw(config, blob.points, labels, &layers, path)
+}
+
+/// Draws just blob as a format image to w, like write_image.
+pub fn write_blob_image<W: Write>(config: &Config,
+                                  blob: &Blob,
+                                  labels: &Vec<String>,
+                                  format: &str,
+                                  w: &mut W,
+                                  ) -> io::Result<()> {
+    let layers = vec![Layer{ blob: blob, color: layer_color(0) }];
+    write_image(config, blob.points, labels, &layers, format, w)
+}
+
+impl<'a> Blob<'a> {
+    /// Draws just this blob to path, like draw_blob.
+    pub fn draw(&self, config: &Config, labels: &Vec<String>, path: &Path) -> io::Result<()> {
+        draw_blob(config, self, labels, path)
+    }
+
+    /// Draws just this blob as a format image to w, like write_blob_image.
+    pub fn write_image<W: Write>(&self, config: &Config, labels: &Vec<String>,
+                                 format: &str, w: &mut W) -> io::Result<()> {
+        write_blob_image(config, self, labels, format, w)
+    }
+}
+
+/// The bytes of a format image of the blobs in layers, as write_image makes.
+pub fn image_bytes(config: &Config,
+                   points: &Vec<Point>,
+                   labels: &Vec<String>,
+                   layers: &Vec<Layer>,
+                   format: &str,
+                   ) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    try!(write_image(config, points, labels, layers, format, &mut bytes));
+    Ok(bytes)
+}
+
+fn render_png(config: &Config,
+              points: &Vec<Point>,
+              labels: &Vec<String>,
+              layers: &Vec<Layer>,
+              ) -> Surface {
     use self::cairo::surface::format::Format;
     let mut surface = Surface::create_image(Format::ARGB32,
                                             config.img.width,
                                             config.img.height);
+    {
+        let mut cr = Cairo::create(&mut surface);
+        render(&mut cr, config, points, labels, layers);
+    }
+    surface
+}
 
-    let mut cr = Cairo::create(&mut surface);
+/// Draws the blobs in layers onto a new page at the end of doc.
+pub fn draw_page(doc: &mut PdfDocument,
+                 config: &Config,
+                 points: &Vec<Point>,
+                 labels: &Vec<String>,
+                 layers: &Vec<Layer>,
+                 ) {
+    let mut page = doc.new_page();
+    render(&mut page, config, points, labels, layers);
+    doc.add_page(page);
+}
 
+/// Draws a page on its own, to be added to a PdfDocument later,
+/// so pages can be drawn on other threads and still go in order.
+pub fn render_page(config: &Config,
+                   points: &Vec<Point>,
+                   labels: &Vec<String>,
+                   layers: &Vec<Layer>,
+                   ) -> PdfCanvas {
+    let mut page = PdfCanvas::new(config.img.width, config.img.height);
+    render(&mut page, config, points, labels, layers);
+    page
+}
 
-    let scale = scale_world(&mut cr, config.img.edge_buffer, config.img.width,
+/// Draws everything config asks for onto the canvas.
+/// Each kind of thing is drawn for every layer before moving on to the next,
+/// so the points end up on top of all the blobs.
+/// Points in any of the layers are coloured as in, the rest as out.
+fn render<C: Canvas>(cr: &mut C,
+                     config: &Config,
+                     points: &Vec<Point>,
+                     labels: &Vec<String>,
+                     layers: &Vec<Layer>,
+                     ) {
+    let scale = scale_world(cr, config.img.edge_buffer, config.img.width,
                             config.img.height, &points);
+    let (inpoints, expoints): (Vec<Index>, Vec<Index>) = (0..points.len()).partition(
+        |&ix| layers.iter().any(|layer| layer.blob.is_in(ix)));
 
 
     if config.draw.background {
@@ -64,46 +231,107 @@ pub fn draw(config: &Config,
 
     if config.draw.polygon {
         cr.set_line_width(config.draw.polygon_thickness / scale);
-        cr.set_source_rgba(hull_color.r, hull_color.g, hull_color.b, 0.8);
-        draw_hull(&mut cr, &points, &hull);
+        for layer in layers {
+            let c = &layer.color;
+            cr.set_source_rgba(c.r, c.g, c.b, 0.8);
+            draw_hull(cr, &points, &layer.blob.hull);
+        }
     }
 
     if config.draw.labels {
-        cr.set_source_rgba(0.,0.,0.,1.);
-        cr.select_font_face("Sans", cairo::font::slant::Slant::Normal,
-                            cairo::font::weight::Weight::Bold);
-        cr.set_font_size(config.draw.fontsize/scale);
-        label_points(&mut cr, &points, &hull);
+        cr.select_font("Sans", true, config.draw.fontsize/scale);
+        for layer in layers {
+            // With one layer the labels stay black, as they always were.
+            if layers.len() == 1 {
+                cr.set_source_rgba(0.,0.,0.,1.);
+            } else {
+                let c = &layer.color;
+                cr.set_source_rgba(c.r, c.g, c.b, 1.);
+            }
+            label_points(cr, &points, &layer.blob.hull, labels);
+        }
     }
 
     if config.draw.blob {
         cr.set_line_width(config.draw.polygon_thickness / scale);
-        cr.set_source_rgba(hull_color.r, hull_color.g, hull_color.b, 0.4);
-        trace_blob(&mut cr, &points, &hull, &inblob, &radii);
-        cr.fill_preserve();
-        cr.set_source_rgba(hull_color.r, hull_color.g, hull_color.b, 0.8);
-        cr.stroke();
+        for layer in layers {
+            let c = &layer.color;
+            cr.set_source_rgba(c.r, c.g, c.b, 0.4);
+            trace_blob(cr, layer.blob);
+            cr.fill_preserve();
+            cr.set_source_rgba(c.r, c.g, c.b, 0.8);
+            cr.stroke();
+        }
     }
 
 
     if config.draw.points {
-        cr.set_source_rgba(inpoints_color.r, inpoints_color.g, inpoints_color.b, 0.9);
-        draw_points(&mut cr, points, inpoints, config.draw.point_radius / scale);
+        let c = Color::from_hex(INPOINTS_COLOR);
+        cr.set_source_rgba(c.r, c.g, c.b, 0.9);
+        draw_points(cr, points, &inpoints, config.draw.point_radius / scale);
 
-        cr.set_source_rgba(expoints_color.r, expoints_color.g, expoints_color.b, 0.9);
-        draw_points(&mut cr, points, expoints, config.draw.point_radius / scale);
+        let c = Color::from_hex(EXPOINTS_COLOR);
+        cr.set_source_rgba(c.r, c.g, c.b, 0.9);
+        draw_points(cr, points, &expoints, config.draw.point_radius / scale);
     }
+}
 
-
-    let filename = util::ascii_path_to_string(path).expect("Filename not ascii?!");
-    surface.write_to_png(filename);
-
+#[test]
+fn test_render_layers() {
+    let config = Config::default();
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.),
+                      Point::new(1., 1.), Point::new(0., 1.)];
+    let points = &points;
+    let blob = move |hull: Vec<Index>| {
+        let mut inblob = vec![false; points.len()];
+        for &i in &hull {
+            inblob[i] = true;
+        }
+        Blob{ points: points, hull: hull, inblob: inblob, radii: vec![0.1; points.len()] }
+    };
+    let blobs = vec![blob(vec![0, 2, 1]), blob(vec![0, 3, 2])];
+    let layers = vec![Layer{ blob: &blobs[0], color: layer_color(0) },
+                      Layer{ blob: &blobs[1], color: layer_color(1) }];
+
+    let mut svg = SvgCanvas::new(100, 100);
+    render(&mut svg, &config, points, &vec![], &layers);
+    let mut out: Vec<u8> = vec![];
+    svg.write_to(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    // Each layer's blob is filled in its own colour.
+    assert!(out.contains("fill=\"rgb(170,170,0)\" fill-opacity=\"0.4\""), "{}", out);
+    assert!(out.contains("fill=\"rgb(51,160,44)\" fill-opacity=\"0.4\""), "{}", out);
+}
+#[test]
+fn test_image_bytes() {
+    let config = Config::default();
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.), Point::new(0., 1.)];
+    let blob = Blob{ points: &points, hull: vec![0, 2, 1], inblob: vec![true; 3],
+                     radii: vec![0.1; 3] };
+    let layers = vec![Layer{ blob: &blob, color: layer_color(0) }];
+
+    let svg = image_bytes(&config, &points, &vec![], &layers, "svg").unwrap();
+    assert!(svg.starts_with(b"<?xml"));
+    let pdf = image_bytes(&config, &points, &vec![], &layers, "pdf").unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+    let eps = image_bytes(&config, &points, &vec![], &layers, "eps").unwrap();
+    assert!(eps.starts_with(b"%!PS"));
+
+    let png = image_bytes(&config, &points, &vec![], &layers, "png").unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+    let err = image_bytes(&config, &points, &vec![], &layers, "jpg").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let mut out = vec![];
+    blob.write_image(&config, &vec![], "svg", &mut out).unwrap();
+    assert_eq!(out, svg);
 }
 
 
 /// This adjusts cairo's user transform such that drawing the points
 /// in their co-ordinate system puts them in the middle of the image.
-fn scale_world(cr: &mut Cairo,
+fn scale_world<C: Canvas>(cr: &mut C,
         boundary: f64, img_width: i32, img_height: i32,
         points: &Vec<Point>) -> f64 {
 
@@ -137,7 +365,7 @@ fn scale_world(cr: &mut Cairo,
 
 /// This traces a polygon with straight lines to cairo
 /// It does not set any cairo options, like fill color or line width.
-fn draw_hull(cr : &mut Cairo,
+fn draw_hull<C: Canvas>(cr : &mut C,
               points: &Vec<Point>,
               hull: &Vec<Index>) {
     cr.new_path();
@@ -148,7 +376,7 @@ fn draw_hull(cr : &mut Cairo,
     cr.stroke();
 }
 
-fn draw_points(cr: &mut Cairo,
+fn draw_points<C: Canvas>(cr: &mut C,
                points: &Vec<Point>,
                indices: &Vec<Index>,
                radius: f64) {
@@ -159,52 +387,38 @@ fn draw_points(cr: &mut Cairo,
     }
 }
 
-fn label_points(cr: &mut Cairo,
+/// Writes each point's label next to it,
+/// or its position in indices if there are no labels.
+fn label_points<C: Canvas>(cr: &mut C,
                 points: &Vec<Point>,
-                indices: &Vec<Index>) {
+                indices: &Vec<Index>,
+                labels: &Vec<String>) {
     for (i,&ix) in indices.iter().enumerate() {
         cr.move_to(points[ix].x, points[ix].y);
-        cr.show_text(&format!(" {}", i));
+        if labels.is_empty() {
+            cr.show_text(&format!(" {}", i));
+        } else {
+            cr.show_text(&format!(" {}", labels[ix]));
+        }
     }
 }
 
-fn trace_blob(cr: &mut Cairo,
-             points: &Vec<Point>,
-             hull: &Vec<Index>,
-             inblob: &Vec<bool>,
-             radii: &Vec<f64>) {
+/// Traces the smooth outline of blob, from geometry::outline.
+fn trace_blob<C: Canvas>(cr: &mut C, blob: &Blob) {
     cr.new_path();
-    let last_ix = hull[hull.len() - 1];
-    let first_ix = hull[0];
-    let (_, mut previous_angle) = smooth_line_angle(
-        &points[last_ix], radii[last_ix], inblob[last_ix],
-        &points[first_ix], radii[first_ix], inblob[first_ix]);
-
-    for hull_ix in 0..hull.len() {
-        let i:      Index = hull[hull_ix];
-        let next_i: Index = hull[(hull_ix + 1) % hull.len()];
-        let a = points[i];
-        let b = points[next_i];
-        trace!("i: {}, {}, a:{:?}, b:{:?}", i, next_i, a, b);
-
-        let a_r = radii[i];
-        let b_r = radii[next_i];
-
-        let a_inblob = inblob[i];
-        let b_inblob = inblob[next_i];
-
-        trace!("a_inblob: {}, b_inblob: {}", a_inblob, b_inblob);
-        let (a_ang, b_ang) = smooth_line_angle(&a, a_r, a_inblob,
-                                               &b, b_r, b_inblob);
-        trace!("a_ang: {}, b_ang: {}", a_ang.to_degrees(), b_ang.to_degrees());
-        if a_inblob {
-            debug!("cr.arc_negative({}, {}, {}, {}, {})", a.x, a.y, a_r, previous_angle.to_degrees(), a_ang.to_degrees());
-            cr.arc_negative(a.x, a.y, a_r, previous_angle, a_ang);
-        } else {
-            debug!("cr.arc({}, {}, {}, {}, {})", a.x, a.y, a_r, previous_angle.to_degrees(), a_ang.to_degrees());
-            cr.arc(a.x, a.y, a_r, previous_angle, a_ang);
+    for segment in geometry::outline(blob) {
+        match segment {
+            Segment::Arc{ centre: a, radius, start, end, direction } => {
+                debug!("Arc around ({}, {}), radius {}, from {} to {}, {:?}", a.x, a.y, radius,
+                       start.to_degrees(), end.to_degrees(), direction);
+                match direction {
+                    Direction::Clockwise => cr.arc_negative(a.x, a.y, radius, start, end),
+                    Direction::CounterClockwise => cr.arc(a.x, a.y, radius, start, end),
+                }
+            },
+            // Each arc starts with a line from where the last one ended.
+            Segment::Line{ .. } => (),
         }
-        previous_angle = b_ang;
     }
     cr.close_path();
 }
@@ -0,0 +1,274 @@
+use draw::canvas::{Canvas, CanvasState};
+use draw::ps::escape_string;
+
+use std::io;
+use std::io::prelude::*;
+
+use tau::TAU;
+
+/// A multi-page PDF, one page per PdfCanvas added to it.
+pub struct PdfDocument {
+    width: i32,
+    height: i32,
+    pages: Vec<String>,
+    // Every opacity used on any page, in thousandths.
+    opacities: Vec<u32>,
+}
+
+impl PdfDocument {
+    pub fn new(width: i32, height: i32) -> PdfDocument {
+        PdfDocument {
+            width: width,
+            height: height,
+            pages: vec![],
+            opacities: vec![],
+        }
+    }
+
+    /// A blank canvas the size of this document's pages.
+    pub fn new_page(&self) -> PdfCanvas {
+        PdfCanvas::new(self.width, self.height)
+    }
+
+    pub fn add_page(&mut self, page: PdfCanvas) {
+        for opacity in page.opacities {
+            if !self.opacities.contains(&opacity) {
+                self.opacities.push(opacity);
+            }
+        }
+        self.pages.push(page.content);
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Writes out the finished document.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        // Objects are 1: catalog, 2: page tree, 3: resources,
+        // then a page and its contents for each page.
+        let mut objects: Vec<String> = vec![];
+
+        objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+
+        let kids: Vec<String> = (0..self.pages.len())
+            .map(|page| format!("{} 0 R", 4 + 2 * page))
+            .collect();
+        objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>",
+                             kids.connect(" "), self.pages.len()));
+
+        let states: Vec<String> = self.opacities.iter()
+            .map(|&opacity| format!("/GS{0} << /Type /ExtGState /ca {1} /CA {1} >>",
+                                    opacity, opacity as f64 / 1000.))
+            .collect();
+        objects.push(format!(
+            "<< /Font << \
+             /F1 << /Type /Font /Subtype /Type1 /BaseFont /Helvetica >> \
+             /F2 << /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold >> \
+             >> /ExtGState << {} >> >>", states.connect(" ")));
+
+        for (page, content) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /Resources 3 0 R \
+                 /MediaBox [0 0 {} {}] /Contents {} 0 R >>",
+                self.width, self.height, 5 + 2 * page));
+            objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream",
+                                 content.len(), content));
+        }
+
+        let mut out: Vec<u8> = vec![];
+        try!(write!(&mut out, "%PDF-1.4\n"));
+        let mut offsets = vec![];
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            try!(write!(&mut out, "{} 0 obj\n{}\nendobj\n", i + 1, object));
+        }
+        let xref = out.len();
+        try!(write!(&mut out, "xref\n0 {}\n", objects.len() + 1));
+        try!(write!(&mut out, "0000000000 65535 f \n"));
+        for offset in offsets {
+            try!(write!(&mut out, "{:010} 00000 n \n", offset));
+        }
+        try!(write!(&mut out, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                    objects.len() + 1, xref));
+        w.write_all(&out)
+    }
+}
+
+/// A Canvas for a single page of a PdfDocument.
+/// PDF has no arcs, so they're made of Bézier curves of at most a quarter turn.
+pub struct PdfCanvas {
+    content: String,
+    opacities: Vec<u32>,
+    state: CanvasState,
+
+    // PDF can't change colours half way through a path, but cairo can,
+    // so paths are kept here until they are stroked or filled.
+    path: String,
+}
+
+impl PdfCanvas {
+    pub fn new(width: i32, height: i32) -> PdfCanvas {
+        let mut canvas = PdfCanvas {
+            content: String::new(),
+            opacities: vec![],
+            state: CanvasState::new(width, height),
+            path: String::new(),
+        };
+        // PDF's y axis points up, cairo's points down.
+        canvas.emit(&format!("1 0 0 -1 0 {} cm", height));
+        canvas
+    }
+
+    fn emit(&mut self, op: &str) {
+        self.content.push_str(op);
+        self.content.push('\n');
+    }
+
+    fn add_arc(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, angle2: f64) {
+        let point = |angle: f64| (xc + radius * angle.cos(), yc + radius * angle.sin());
+        let start = point(angle1);
+        self.line_to(start.0, start.1);
+
+        let sweep = angle2 - angle1;
+        let pieces = (sweep.abs() / (TAU / 4.)).ceil() as usize;
+        for piece in 0..pieces {
+            let a = angle1 + sweep * piece as f64 / pieces as f64;
+            let b = angle1 + sweep * (piece + 1) as f64 / pieces as f64;
+            // The control points are along the tangents, k * radius away.
+            let k = 4. / 3. * ((b - a) / 4.).tan();
+            let (p0, p3) = (point(a), point(b));
+            let p1 = (p0.0 - k * radius * a.sin(), p0.1 + k * radius * a.cos());
+            let p2 = (p3.0 + k * radius * b.sin(), p3.1 - k * radius * b.cos());
+            self.path.push_str(&format!("{} {} {} {} {} {} c\n",
+                                        p1.0, p1.1, p2.0, p2.1, p3.0, p3.1));
+        }
+        let end = point(angle2);
+        self.state.move_to(end.0, end.1);
+    }
+
+    fn paint_path(&mut self, op: &str) {
+        self.content.push_str(&self.path);
+        self.emit(op);
+    }
+}
+
+impl Canvas for PdfCanvas {
+    fn scale(&mut self, sx: f64, sy: f64) {
+        self.state.scale(sx, sy);
+        self.emit(&format!("{} 0 0 {} 0 0 cm", sx, sy));
+    }
+    fn translate(&mut self, tx: f64, ty: f64) {
+        self.state.translate(tx, ty);
+        self.emit(&format!("1 0 0 1 {} {} cm", tx, ty));
+    }
+
+    fn set_source_rgba(&mut self, r: f64, g: f64, b: f64, a: f64) {
+        let opacity = (a * 1000.).round() as u32;
+        if !self.opacities.contains(&opacity) {
+            self.opacities.push(opacity);
+        }
+        self.emit(&format!("{0} {1} {2} RG {0} {1} {2} rg /GS{3} gs", r, g, b, opacity));
+    }
+    fn set_line_width(&mut self, width: f64) {
+        self.emit(&format!("{} w", width));
+    }
+    fn select_font(&mut self, _family: &str, bold: bool, size: f64) {
+        let font = if bold { "F2" } else { "F1" };
+        self.emit(&format!("BT /{} {} Tf ET", font, size));
+    }
+
+    fn paint(&mut self) {
+        let (x, y, width, height) = self.state.surface_rect();
+        self.emit(&format!("{} {} {} {} re f", x, y, width, height));
+    }
+
+    fn new_path(&mut self) {
+        self.path.clear();
+        self.state.new_path();
+    }
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.path.push_str(&format!("{} {} m\n", x, y));
+        self.state.move_to(x, y);
+    }
+    fn line_to(&mut self, x: f64, y: f64) {
+        let op = if self.state.line_to(x, y) { "l" } else { "m" };
+        self.path.push_str(&format!("{} {} {}\n", x, y, op));
+    }
+    fn arc(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, mut angle2: f64) {
+        while angle2 < angle1 {
+            angle2 = angle2 + TAU;
+        }
+        self.add_arc(xc, yc, radius, angle1, angle2);
+    }
+    fn arc_negative(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, mut angle2: f64) {
+        while angle2 > angle1 {
+            angle2 = angle2 - TAU;
+        }
+        self.add_arc(xc, yc, radius, angle1, angle2);
+    }
+    fn close_path(&mut self) {
+        self.path.push_str("h\n");
+    }
+
+    fn stroke(&mut self) {
+        self.paint_path("S");
+        self.new_path();
+    }
+    fn fill(&mut self) {
+        self.paint_path("f");
+        self.new_path();
+    }
+    fn fill_preserve(&mut self) {
+        self.paint_path("f");
+    }
+    fn show_text(&mut self, text: &str) {
+        // Text has to be placed explicitly, at where the path ended.
+        let (x, y) = match self.state.current {
+            Some(p) => p,
+            None => return,
+        };
+        // Flip the text back upright.
+        self.emit(&format!("BT 1 0 0 -1 {} {} Tm ({}) Tj ET", x, y, escape_string(text)));
+    }
+}
+
+#[test]
+fn test_pdf_pages() {
+    let mut doc = PdfDocument::new(300, 200);
+    for _ in 0..3 {
+        let mut page = doc.new_page();
+        page.set_source_rgba(0., 0., 1., 0.4);
+        page.new_path();
+        page.arc(10., 10., 5., 0., TAU);
+        page.fill();
+        page.move_to(1., 2.);
+        page.show_text("(3)");
+        doc.add_page(page);
+    }
+    assert_eq!(doc.page_count(), 3);
+
+    let mut out: Vec<u8> = vec![];
+    doc.write_to(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("%PDF-1.4\n"));
+    assert!(out.contains("/Count 3"));
+    assert!(out.contains("/GS400 << /Type /ExtGState /ca 0.4 /CA 0.4 >>"));
+    assert!(out.contains("BT 1 0 0 -1 1 2 Tm (\\(3\\)) Tj ET"), "{}", out);
+    // A full circle is four curves.
+    assert_eq!(out.split(" c\n").count() - 1, 3 * 4);
+
+    // The cross reference table points at the objects.
+    let xref = out.find("xref\n").unwrap();
+    assert!(out.ends_with(&format!("startxref\n{}\n%%EOF\n", xref)));
+    let first = out.find("1 0 obj").unwrap();
+    assert!(out.contains(&format!("{:010} 00000 n \n", first)));
+}
+#[test]
+fn test_pdf_arc_endpoints() {
+    let mut page = PdfCanvas::new(10, 10);
+    page.new_path();
+    page.arc_negative(0., 0., 2., TAU / 4., 0.);
+    assert_eq!(page.state.current, Some((2., 0.)));
+    assert!(page.path.ends_with(" 2 0 c\n"), "{}", page.path);
+}
@@ -0,0 +1,145 @@
+//! Just enough of a PNG encoder to write out a cairo image surface
+//! to any io::Write, since cairo itself can only write PNGs to a file.
+//! The image data is stored, not compressed, so there's nothing to get wrong.
+
+use std::io;
+use std::io::prelude::*;
+
+static SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// The most a stored deflate block can hold.
+const MAX_BLOCK: usize = 65535;
+
+/// Writes a width by height image as a PNG to w.
+/// Data is in cairo's ARGB32 format: rows stride bytes apart, each pixel a
+/// native endian u32 with the colour premultiplied by the alpha.
+pub fn write_png<W: Write>(w: &mut W, width: usize, height: usize, stride: usize,
+                           data: &[u8]) -> io::Result<()> {
+    if width == 0 || height == 0 || stride < 4 * width || data.len() < stride * height {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "image data doesn't fit the size"));
+    }
+    try!(w.write_all(&SIGNATURE));
+
+    let mut header = vec![];
+    push_u32(&mut header, width as u32);
+    push_u32(&mut header, height as u32);
+    // 8 bits per channel, RGBA, default compression, filtering and no interlace.
+    header.extend([8, 6, 0, 0, 0].iter().cloned());
+    try!(write_chunk(w, b"IHDR", &header));
+
+    // Each row starts with its filter type, 0 for none.
+    let mut raw = Vec::with_capacity((4 * width + 1) * height);
+    for row in data.chunks(stride).take(height) {
+        raw.push(0);
+        for pixel in row[..4 * width].chunks(4) {
+            let bytes = if cfg!(target_endian = "little") {
+                [pixel[3], pixel[2], pixel[1], pixel[0]]
+            } else {
+                [pixel[0], pixel[1], pixel[2], pixel[3]]
+            };
+            let argb = (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16
+                | (bytes[2] as u32) << 8 | bytes[3] as u32;
+            raw.extend(unpremultiply(argb).iter().cloned());
+        }
+    }
+    try!(write_chunk(w, b"IDAT", &zlib_stored(&raw)));
+    write_chunk(w, b"IEND", &[])
+}
+
+/// Turns a premultiplied ARGB pixel into straight RGBA bytes.
+fn unpremultiply(argb: u32) -> [u8; 4] {
+    let a = argb >> 24;
+    let channel = |shift: u32| {
+        let c = (argb >> shift) & 0xff;
+        if a == 0 { 0 } else { ((c * 255 + a / 2) / a) as u8 }
+    };
+    [channel(16), channel(8), channel(0), a as u8]
+}
+#[test]
+fn test_unpremultiply() {
+    assert_eq!(unpremultiply(0xff336699), [0x33, 0x66, 0x99, 0xff]);
+    assert_eq!(unpremultiply(0x80400000), [0x80, 0, 0, 0x80]);
+    assert_eq!(unpremultiply(0x00000000), [0, 0, 0, 0]);
+}
+
+fn push_u32(out: &mut Vec<u8>, x: u32) {
+    out.extend([(x >> 24) as u8, (x >> 16) as u8, (x >> 8) as u8, x as u8].iter().cloned());
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8], data: &[u8]) -> io::Result<()> {
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    push_u32(&mut chunk, data.len() as u32);
+    chunk.extend(kind.iter().cloned());
+    chunk.extend(data.iter().cloned());
+    let crc = crc32(&chunk[4..]);
+    push_u32(&mut chunk, crc);
+    w.write_all(&chunk)
+}
+
+/// Wraps data in a zlib stream of stored deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    // Even no data needs one block, to mark the end.
+    let blocks: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(MAX_BLOCK).collect()
+    };
+    for (i, block) in blocks.iter().enumerate() {
+        let last = if i + 1 == blocks.len() { 1 } else { 0 };
+        let len = block.len() as u16;
+        out.extend([last, len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]
+                   .iter().cloned());
+        out.extend(block.iter().cloned());
+    }
+    push_u32(&mut out, adler32(data));
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+#[test]
+fn test_adler32() {
+    assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    assert_eq!(adler32(b""), 1);
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc = crc ^ byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b"IEND"), 0xae426082);
+    assert_eq!(crc32(b"123456789"), 0xcbf43926);
+}
+
+#[test]
+fn test_write_png() {
+    // Two pixels, opaque red and transparent.
+    let data = if cfg!(target_endian = "little") {
+        [0, 0, 0xff, 0xff, 0, 0, 0, 0]
+    } else {
+        [0xff, 0xff, 0, 0, 0, 0, 0, 0]
+    };
+    let mut out = vec![];
+    write_png(&mut out, 2, 1, 8, &data).unwrap();
+    assert!(out.starts_with(&SIGNATURE));
+    assert!(out.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+    // The IHDR chunk says 2 by 1.
+    assert_eq!(&out[8..24], &[0, 0, 0, 13, b'I', b'H', b'D', b'R', 0, 0, 0, 2, 0, 0, 0, 1]);
+    assert!(write_png(&mut vec![], 3, 1, 8, &data).is_err());
+}
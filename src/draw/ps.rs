@@ -0,0 +1,148 @@
+use draw::canvas::{Canvas, CanvasState};
+
+use std::io;
+use std::io::prelude::*;
+
+/// A Canvas that builds up an Encapsulated PostScript figure,
+/// for including straight into LaTeX.
+/// PostScript has no transparency, so colours are blended with white instead.
+pub struct PsCanvas {
+    width: i32,
+    height: i32,
+    body: String,
+    state: CanvasState,
+}
+
+impl PsCanvas {
+    pub fn new(width: i32, height: i32) -> PsCanvas {
+        PsCanvas {
+            width: width,
+            height: height,
+            body: String::new(),
+            state: CanvasState::new(width, height),
+        }
+    }
+
+    /// Writes out the finished figure.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write!(w, "%!PS-Adobe-3.0 EPSF-3.0\n"));
+        try!(write!(w, "%%BoundingBox: 0 0 {} {}\n", self.width, self.height));
+        try!(write!(w, "%%Creator: rust-blob\n"));
+        try!(write!(w, "%%Pages: 1\n"));
+        try!(write!(w, "%%EndComments\n"));
+        // PostScript's y axis points up, cairo's points down.
+        try!(write!(w, "0 {} translate 1 -1 scale\n", self.height));
+        try!(w.write_all(self.body.as_bytes()));
+        try!(write!(w, "showpage\n%%EOF\n"));
+        Ok(())
+    }
+
+    fn emit(&mut self, op: &str) {
+        self.body.push_str(op);
+        self.body.push('\n');
+    }
+}
+
+/// Escapes a string for use as a PostScript or PDF string literal.
+pub fn escape_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Canvas for PsCanvas {
+    fn scale(&mut self, sx: f64, sy: f64) {
+        self.state.scale(sx, sy);
+        self.emit(&format!("{} {} scale", sx, sy));
+    }
+    fn translate(&mut self, tx: f64, ty: f64) {
+        self.state.translate(tx, ty);
+        self.emit(&format!("{} {} translate", tx, ty));
+    }
+
+    fn set_source_rgba(&mut self, r: f64, g: f64, b: f64, a: f64) {
+        let blend = |c: f64| 1. - a * (1. - c);
+        self.emit(&format!("{} {} {} setrgbcolor", blend(r), blend(g), blend(b)));
+    }
+    fn set_line_width(&mut self, width: f64) {
+        self.emit(&format!("{} setlinewidth", width));
+    }
+    fn select_font(&mut self, _family: &str, bold: bool, size: f64) {
+        let font = if bold { "Helvetica-Bold" } else { "Helvetica" };
+        self.emit(&format!("/{} findfont {} scalefont setfont", font, size));
+    }
+
+    fn paint(&mut self) {
+        let (x, y, width, height) = self.state.surface_rect();
+        self.emit(&format!("{} {} {} {} rectfill", x, y, width, height));
+    }
+
+    fn new_path(&mut self) {
+        self.emit("newpath");
+        self.state.new_path();
+    }
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.emit(&format!("{} {} moveto", x, y));
+        self.state.move_to(x, y);
+    }
+    fn line_to(&mut self, x: f64, y: f64) {
+        let op = if self.state.line_to(x, y) { "lineto" } else { "moveto" };
+        self.emit(&format!("{} {} {}", x, y, op));
+    }
+    fn arc(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, angle2: f64) {
+        self.emit(&format!("{} {} {} {} {} arc", xc, yc, radius,
+                           angle1.to_degrees(), angle2.to_degrees()));
+        self.state.move_to(xc + radius * angle2.cos(), yc + radius * angle2.sin());
+    }
+    fn arc_negative(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, angle2: f64) {
+        self.emit(&format!("{} {} {} {} {} arcn", xc, yc, radius,
+                           angle1.to_degrees(), angle2.to_degrees()));
+        self.state.move_to(xc + radius * angle2.cos(), yc + radius * angle2.sin());
+    }
+    fn close_path(&mut self) {
+        self.emit("closepath");
+    }
+
+    fn stroke(&mut self) {
+        self.emit("stroke");
+        self.state.new_path();
+    }
+    fn fill(&mut self) {
+        self.emit("fill");
+        self.state.new_path();
+    }
+    fn fill_preserve(&mut self) {
+        self.emit("gsave fill grestore");
+    }
+    fn show_text(&mut self, text: &str) {
+        // Flip the text back upright around the current point.
+        self.emit(&format!("gsave 1 -1 scale ({}) show grestore", escape_string(text)));
+    }
+}
+
+#[test]
+fn test_ps_figure() {
+    let mut ps = PsCanvas::new(200, 100);
+    ps.set_source_rgba(1., 0., 0., 0.5);
+    ps.new_path();
+    ps.arc_negative(0., 0., 1., 0., -1.);
+    ps.stroke();
+    ps.move_to(1., 1.);
+    ps.show_text("(a)");
+
+    let mut out: Vec<u8> = vec![];
+    ps.write_to(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 0 0 200 100\n"));
+    assert!(out.contains("1 0.5 0.5 setrgbcolor"), "{}", out);
+    assert!(out.contains(" arcn\nstroke\n"), "{}", out);
+    assert!(out.contains("(\\(a\\)) show"), "{}", out);
+}
@@ -0,0 +1,224 @@
+use draw::canvas::{Canvas, CanvasState};
+
+use std::io;
+use std::io::prelude::*;
+
+use tau::TAU;
+
+/// A Canvas that builds up an SVG document.
+/// Arcs stay arcs, so the blob comes out as exact circles and tangent lines.
+pub struct SvgCanvas {
+    width: i32,
+    height: i32,
+    body: String,
+    state: CanvasState,
+
+    rgba: (f64, f64, f64, f64),
+    line_width: f64,
+    font: (String, bool, f64),
+
+    path: String,
+}
+
+impl SvgCanvas {
+    pub fn new(width: i32, height: i32) -> SvgCanvas {
+        SvgCanvas {
+            width: width,
+            height: height,
+            body: String::new(),
+            state: CanvasState::new(width, height),
+            rgba: (0., 0., 0., 1.),
+            line_width: 2.,
+            font: ("Sans".to_string(), false, 10.),
+            path: String::new(),
+        }
+    }
+
+    /// Writes out the finished document.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        try!(write!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+        try!(write!(w, "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                        width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+                    self.width, self.height));
+        try!(w.write_all(self.body.as_bytes()));
+        try!(write!(w, "</svg>\n"));
+        Ok(())
+    }
+
+    fn transform(&self) -> String {
+        let state = &self.state;
+        format!("matrix({} 0 0 {} {} {})", state.xx, state.yy, state.x0, state.y0)
+    }
+
+    fn color(&self) -> String {
+        let (r, g, b, _) = self.rgba;
+        format!("rgb({},{},{})",
+                (r * 255.).round(), (g * 255.).round(), (b * 255.).round())
+    }
+
+    fn add_arc(&mut self, xc: f64, yc: f64, radius: f64,
+               angle1: f64, angle2: f64, positive: bool) {
+        let start = (xc + radius * angle1.cos(), yc + radius * angle1.sin());
+        self.line_to(start.0, start.1);
+        // An SVG arc can't go all the way around,
+        // so split it into pieces of at most half a turn.
+        let sweep = angle2 - angle1;
+        let pieces = (sweep.abs() / (TAU / 2.)).ceil() as usize;
+        let flag = if positive { 1 } else { 0 };
+        for piece in 1..pieces + 1 {
+            let angle = angle1 + sweep * piece as f64 / pieces as f64;
+            self.path.push_str(&format!("A {0} {0} 0 0 {1} {2} {3} ",
+                                        radius, flag,
+                                        xc + radius * angle.cos(),
+                                        yc + radius * angle.sin()));
+        }
+        self.state.move_to(xc + radius * angle2.cos(), yc + radius * angle2.sin());
+    }
+
+    fn emit_path(&mut self, fill: bool) {
+        if self.path.is_empty() {
+            return;
+        }
+        let paint = if fill {
+            format!("fill=\"{}\" fill-opacity=\"{}\" stroke=\"none\"",
+                    self.color(), self.rgba.3)
+        } else {
+            format!("fill=\"none\" stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\"",
+                    self.color(), self.rgba.3, self.line_width)
+        };
+        let element = format!("<path transform=\"{}\" d=\"{}\" {}/>\n",
+                              self.transform(), self.path.trim(), paint);
+        self.body.push_str(&element);
+    }
+}
+
+/// Escapes the characters that mean something in XML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Canvas for SvgCanvas {
+    fn scale(&mut self, sx: f64, sy: f64) {
+        self.state.scale(sx, sy);
+    }
+    fn translate(&mut self, tx: f64, ty: f64) {
+        self.state.translate(tx, ty);
+    }
+
+    fn set_source_rgba(&mut self, r: f64, g: f64, b: f64, a: f64) {
+        self.rgba = (r, g, b, a);
+    }
+    fn set_line_width(&mut self, width: f64) {
+        self.line_width = width;
+    }
+    fn select_font(&mut self, family: &str, bold: bool, size: f64) {
+        self.font = (family.to_string(), bold, size);
+    }
+
+    fn paint(&mut self) {
+        // Untransformed, the surface is just 0, 0 to width, height.
+        let element = format!("<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" \
+                               fill=\"{}\" fill-opacity=\"{}\"/>\n",
+                              self.width, self.height, self.color(), self.rgba.3);
+        self.body.push_str(&element);
+    }
+
+    fn new_path(&mut self) {
+        self.path.clear();
+        self.state.new_path();
+    }
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.path.push_str(&format!("M {} {} ", x, y));
+        self.state.move_to(x, y);
+    }
+    fn line_to(&mut self, x: f64, y: f64) {
+        let op = if self.state.line_to(x, y) { "L" } else { "M" };
+        self.path.push_str(&format!("{} {} {} ", op, x, y));
+    }
+    fn arc(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, mut angle2: f64) {
+        while angle2 < angle1 {
+            angle2 = angle2 + TAU;
+        }
+        self.add_arc(xc, yc, radius, angle1, angle2, true);
+    }
+    fn arc_negative(&mut self, xc: f64, yc: f64, radius: f64, angle1: f64, mut angle2: f64) {
+        while angle2 > angle1 {
+            angle2 = angle2 - TAU;
+        }
+        self.add_arc(xc, yc, radius, angle1, angle2, false);
+    }
+    fn close_path(&mut self) {
+        self.path.push_str("Z ");
+    }
+
+    fn stroke(&mut self) {
+        self.emit_path(false);
+        self.new_path();
+    }
+    fn fill(&mut self) {
+        self.emit_path(true);
+        self.new_path();
+    }
+    fn fill_preserve(&mut self) {
+        self.emit_path(true);
+    }
+    fn show_text(&mut self, text: &str) {
+        let (x, y) = match self.state.current {
+            Some(p) => p,
+            None => return,
+        };
+        let element = format!("<text transform=\"{}\" x=\"{}\" y=\"{}\" \
+                               font-family=\"{}\" font-weight=\"{}\" font-size=\"{}\" \
+                               fill=\"{}\" fill-opacity=\"{}\" \
+                               xml:space=\"preserve\">{}</text>\n",
+                              self.transform(), x, y,
+                              escape(&self.font.0),
+                              if self.font.1 { "bold" } else { "normal" },
+                              self.font.2,
+                              self.color(), self.rgba.3, escape(text));
+        self.body.push_str(&element);
+    }
+}
+
+#[test]
+fn test_svg_arc() {
+    let mut svg = SvgCanvas::new(100, 100);
+    svg.set_source_rgba(1., 0., 0., 0.5);
+    svg.new_path();
+    svg.arc(0., 0., 1., 0., TAU / 4.);
+    svg.stroke();
+    let mut out: Vec<u8> = vec![];
+    svg.write_to(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("d=\"M 1 0 A 1 1 0 0 1 "), "{}", out);
+    assert!(out.contains("stroke=\"rgb(255,0,0)\" stroke-opacity=\"0.5\""), "{}", out);
+}
+#[test]
+fn test_svg_full_circle() {
+    let mut svg = SvgCanvas::new(100, 100);
+    svg.new_path();
+    svg.arc_negative(0., 0., 1., TAU, 0.);
+    svg.fill();
+    // Two halves, as one arc back to the start would be empty.
+    assert_eq!(svg.body.split("A 1 1 0 0 0").count() - 1, 2);
+}
+#[test]
+fn test_svg_transform() {
+    let mut svg = SvgCanvas::new(100, 100);
+    svg.scale(2., 3.);
+    svg.translate(5., 1.);
+    svg.move_to(0., 0.);
+    svg.show_text("a<b");
+    assert!(svg.body.contains("matrix(2 0 0 3 10 3)"), "{}", svg.body);
+    assert!(svg.body.contains(">a&lt;b</text>"), "{}", svg.body);
+}
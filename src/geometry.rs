@@ -0,0 +1,697 @@
+//! The exact shape of a blob's outline, as arcs around the hull points
+//! joined by straight lines tangent to them, for anything that wants the
+//! shape itself rather than a picture of it.
+
+use blob::Blob;
+use types::{Point, Vector, Index, Radius, rotate_ccw};
+
+use std::cmp;
+use std::f64;
+
+use tau::TAU;
+
+/// Which way an arc goes round its centre,
+/// with y pointing up as in the points files.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// A piece of a blob's outline.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Segment {
+    /// Part of the circle of radius around a hull point.
+    /// Angles are in radians from the x axis, and end is reached from start
+    /// going in direction, so it is never past start the other way.
+    Arc { centre: Point, radius: Radius, start: f64, end: f64, direction: Direction },
+    /// A line tangent to the arcs on either end of it.
+    Line { from: Point, to: Point },
+}
+
+fn point_at(centre: Point, radius: Radius, angle: f64) -> Point {
+    Point::new(centre.x + radius * angle.cos(), centre.y + radius * angle.sin())
+}
+
+impl Segment {
+    /// Where the segment starts.
+    pub fn start_point(&self) -> Point {
+        match *self {
+            Segment::Arc{ centre, radius, start, .. } => point_at(centre, radius, start),
+            Segment::Line{ from, .. } => from,
+        }
+    }
+
+    /// Where the segment ends.
+    pub fn end_point(&self) -> Point {
+        match *self {
+            Segment::Arc{ centre, radius, end, .. } => point_at(centre, radius, end),
+            Segment::Line{ to, .. } => to,
+        }
+    }
+
+    /// How long the segment is.
+    pub fn length(&self) -> f64 {
+        use na::Norm;
+        match *self {
+            Segment::Arc{ radius, start, end, .. } => radius * (end - start).abs(),
+            Segment::Line{ from, to } => (to - from).norm(),
+        }
+    }
+}
+
+/// The outline of blob, going clockwise like its hull.
+/// Each hull point has an arc, followed by the line to the next one's arc,
+/// so the last line ends where the first arc starts.
+/// Arcs go clockwise around points in the blob, and counter clockwise
+/// around the points it dents in to avoid.
+/// A blob of one point is just the circle around it, and of none, nothing.
+pub fn outline(blob: &Blob) -> Vec<Segment> {
+    let (points, hull) = (blob.points, &blob.hull);
+    match hull.len() {
+        0 => return vec![],
+        1 => return vec![Segment::Arc{ centre: points[hull[0]], radius: blob.radii[hull[0]],
+                                       start: 0., end: -TAU,
+                                       direction: Direction::Clockwise }],
+        _ => (),
+    }
+    let ends = |i: Index, j: Index| smooth_line_angle(
+        &points[i], blob.radii[i], blob.inblob[i],
+        &points[j], blob.radii[j], blob.inblob[j]);
+
+    let mut segments = Vec::with_capacity(2 * hull.len());
+    let (_, mut previous_angle) = ends(hull[hull.len() - 1], hull[0]);
+    for hull_ix in 0..hull.len() {
+        let i = hull[hull_ix];
+        let next_i = hull[(hull_ix + 1) % hull.len()];
+        let (a, a_r) = (points[i], blob.radii[i]);
+        let (b, b_r) = (points[next_i], blob.radii[next_i]);
+
+        let (a_ang, b_ang) = ends(i, next_i);
+        trace!("i: {}, {}, a_ang: {}, b_ang: {}", i, next_i,
+               a_ang.to_degrees(), b_ang.to_degrees());
+        let (mut end, direction) = (a_ang, if blob.inblob[i] {
+            Direction::Clockwise
+        } else {
+            Direction::CounterClockwise
+        });
+        // Same as cairo, go round the circle to get to the end.
+        if direction == Direction::Clockwise {
+            while end > previous_angle { end = end - TAU; }
+        } else {
+            while end < previous_angle { end = end + TAU; }
+        }
+        segments.push(Segment::Arc{ centre: a, radius: a_r, start: previous_angle, end: end,
+                                    direction: direction });
+        segments.push(Segment::Line{ from: point_at(a, a_r, a_ang),
+                                     to: point_at(b, b_r, b_ang) });
+        previous_angle = b_ang;
+    }
+    segments
+}
+#[test]
+fn test_outline_square() {
+    use na;
+    // Clockwise round a unit square.
+    let points = vec![Point::new(0., 0.), Point::new(0., 1.),
+                      Point::new(1., 1.), Point::new(1., 0.)];
+    let blob = Blob{ points: &points, hull: vec![0, 1, 2, 3], inblob: vec![true; 4],
+                     radii: vec![0.1; 4] };
+    let segments = outline(&blob);
+    assert_eq!(segments.len(), 8);
+    for (k, segment) in segments.iter().enumerate() {
+        let next = &segments[(k + 1) % segments.len()];
+        assert!(na::approx_eq(&segment.end_point(), &next.start_point()),
+                "Segment {} ends at {:?}, but the next starts at {:?}",
+                k, segment.end_point(), next.start_point());
+        match *segment {
+            // Each corner turns right by a quarter.
+            Segment::Arc{ start, end, direction, .. } => {
+                assert_eq!(direction, Direction::Clockwise);
+                assert!(na::approx_eq(&(end - start), &(-TAU / 4.)));
+            },
+            Segment::Line{ .. } => assert!(na::approx_eq(&segment.length(), &1.)),
+        }
+    }
+    // The first line runs up the left side, outside the square.
+    assert!(na::approx_eq(&segments[1].start_point(), &Point::new(-0.1, 0.)));
+}
+#[test]
+fn test_outline_degenerate() {
+    use na;
+    let points = vec![Point::new(1., 2.), Point::new(5., 5.)];
+    let none = Blob{ points: &points, hull: vec![], inblob: vec![false; 2], radii: vec![0.5; 2] };
+    assert!(outline(&none).is_empty());
+    assert!(!none.contains(&points[0]));
+
+    let one = Blob{ points: &points, hull: vec![0], inblob: vec![true, false],
+                    radii: vec![0.5; 2] };
+    let segments = outline(&one);
+    assert_eq!(segments.len(), 1);
+    assert!(na::approx_eq(&segments[0].length(), &(TAU * 0.5)));
+    assert!(one.contains(&points[0]) && !one.contains(&points[1]));
+    assert!(na::approx_eq(&one.signed_distance(&points[0]), &-0.5));
+    let measures = one.measures();
+    assert!(na::approx_eq(&measures.area, &(TAU / 2. * 0.25)));
+    assert!(na::approx_eq(&measures.centroid, &points[0]));
+}
+#[test]
+fn test_outline_dent() {
+    // The bottom middle point is left out, so the outline goes round above it.
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.5), Point::new(2., 0.),
+                      Point::new(1., 2.)];
+    let blob = Blob{ points: &points, hull: vec![0, 3, 2, 1],
+                     inblob: vec![true, false, true, true], radii: vec![0.1; 4] };
+    let segments = outline(&blob);
+    match segments[6] {
+        Segment::Arc{ centre, start, end, direction, .. } => {
+            assert_eq!(centre, points[1]);
+            assert_eq!(direction, Direction::CounterClockwise);
+            assert!(start <= end);
+            assert!(((start + end) / 2.).sin() > 0.);
+        },
+        ref other => panic!("Expected an arc, got {:?}", other),
+    }
+}
+
+
+/// How many times the outline goes round p, counter clockwise.
+/// This is -1 inside a blob, since outlines go clockwise, and 0 outside.
+/// Points on the outline could go either way.
+pub fn winding_number(segments: &Vec<Segment>, p: &Point) -> i32 {
+    use na::Norm;
+    let mut turned = 0.;
+    for segment in segments {
+        let (u, v) = (segment.start_point() - *p, segment.end_point() - *p);
+        let mut angle = (u.x * v.y - u.y * v.x).atan2(u.x * v.x + u.y * v.y);
+        if let Segment::Arc{ centre, radius, start, end, .. } = *segment {
+            // From inside the circle the arc keeps turning the same way,
+            // possibly by more than half a turn.
+            // From outside, it turns the same as its chord.
+            if (*p - centre).norm() < radius {
+                // A whole circle ends where it starts, but still goes round.
+                if (end - start).abs() >= TAU {
+                    angle = if end > start { TAU } else { -TAU };
+                } else if end > start && angle < 0. {
+                    angle = angle + TAU;
+                } else if end < start && angle > 0. {
+                    angle = angle - TAU;
+                }
+            }
+        }
+        turned = turned + angle;
+    }
+    (turned / TAU).round() as i32
+}
+
+/// How far p is from the closest point of segment.
+pub fn distance(segment: &Segment, p: &Point) -> f64 {
+    use na::{Dot, Norm};
+    match *segment {
+        Segment::Line{ from, to } => {
+            let along = to - from;
+            let t = if along.sqnorm() == 0. {
+                0.
+            } else {
+                ((*p - from).dot(&along) / along.sqnorm()).max(0.).min(1.)
+            };
+            (*p - (from + along * t)).norm()
+        },
+        Segment::Arc{ centre, radius, start, end, .. } => {
+            let angle = (p.y - centre.y).atan2(p.x - centre.x);
+            // How far round the arc p's angle is from its start.
+            let round = if end >= start {
+                normalize_angle(angle - start)
+            } else {
+                normalize_angle(start - angle)
+            };
+            if round <= (end - start).abs() {
+                ((*p - centre).norm() - radius).abs()
+            } else {
+                let (a, b) = (segment.start_point(), segment.end_point());
+                (*p - a).norm().min((*p - b).norm())
+            }
+        },
+    }
+}
+
+/// How far p is from the outline, negative when p is inside it.
+pub fn signed_distance(segments: &Vec<Segment>, p: &Point) -> f64 {
+    let closest = segments.iter()
+        .map(|segment| distance(segment, p))
+        .fold(f64::INFINITY, |a, b| a.min(b));
+    if winding_number(segments, p) != 0 { -closest } else { closest }
+}
+#[test]
+fn test_signed_distance_square() {
+    let points = vec![Point::new(0., 0.), Point::new(0., 1.),
+                      Point::new(1., 1.), Point::new(1., 0.)];
+    let blob = Blob{ points: &points, hull: vec![0, 1, 2, 3], inblob: vec![true; 4],
+                     radii: vec![0.1; 4] };
+    let segments = outline(&blob);
+    let check = |x: f64, y: f64, expected: f64| {
+        let found = signed_distance(&segments, &Point::new(x, y));
+        assert!((found - expected).abs() < 1e-9,
+                "({}, {}) should be {} from the outline, not {}", x, y, expected, found);
+    };
+    check(0.5, 0.5, -0.6);
+    check(-0.05, 0.5, -0.05);
+    check(-0.15, 0.5, 0.05);
+    check(0.5, 1.05, -0.05);
+    // Round the corner, the edge is 0.1 from the corner point.
+    check(-0.06, -0.06, 0.06 * 2f64.sqrt() - 0.1);
+    check(-0.08, -0.08, 0.08 * 2f64.sqrt() - 0.1);
+    assert_eq!(winding_number(&segments, &Point::new(0.5, 0.5)), -1);
+    assert_eq!(winding_number(&segments, &Point::new(2., 2.)), 0);
+}
+#[test]
+fn test_signed_distance_dent() {
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.5), Point::new(2., 0.),
+                      Point::new(1., 2.)];
+    let blob = Blob{ points: &points, hull: vec![0, 3, 2, 1],
+                     inblob: vec![true, false, true, true], radii: vec![0.1; 4] };
+    let segments = outline(&blob);
+    // The excluded point has its disc carved out of the blob.
+    assert!((signed_distance(&segments, &points[1]) - 0.1).abs() < 1e-9);
+    assert!((signed_distance(&segments, &Point::new(1., 0.55)) - 0.05).abs() < 1e-9);
+    assert!((signed_distance(&segments, &Point::new(1., 0.65)) + 0.05).abs() < 1e-9);
+}
+
+/// How big a shape is, and where its middle is.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Measures {
+    pub area: f64,
+    pub perimeter: f64,
+    /// The centre of mass of the area inside.
+    pub centroid: Point,
+}
+
+/// The measures of the shape inside a closed outline, going either way round.
+/// These are exact, working round the outline with Green's theorem,
+/// rather than from a polyline.
+/// An outline with no area, like the hull of one point or two,
+/// has the centroid of its segments' start points instead.
+pub fn measures(segments: &Vec<Segment>) -> Measures {
+    // Twice the signed area, and twice the signed area times the centroid.
+    let (mut area2, mut moment_x, mut moment_y) = (0., 0., 0.);
+    for segment in segments {
+        match *segment {
+            Segment::Line{ from: p, to: q } => {
+                area2 = area2 + p.x * q.y - q.x * p.y;
+                moment_x = moment_x + (q.y - p.y) * (p.x * p.x + p.x * q.x + q.x * q.x) / 3.;
+                moment_y = moment_y - (q.x - p.x) * (p.y * p.y + p.y * q.y + q.y * q.y) / 3.;
+            },
+            Segment::Arc{ centre: c, radius: r, start, end, .. } => {
+                let change = |f: &Fn(f64) -> f64| f(end) - f(start);
+                let sin = change(&|t: f64| t.sin());
+                let cos = change(&|t: f64| t.cos());
+                area2 = area2 + r * c.x * sin - r * c.y * cos + r * r * (end - start);
+                // The integrals of x^2 dy and y^2 dx round the arc.
+                moment_x = moment_x + c.x * c.x * r * sin
+                    + 2. * c.x * r * r * change(&|t: f64| t / 2. + (2. * t).sin() / 4.)
+                    + r * r * r * change(&|t: f64| t.sin() - t.sin().powi(3) / 3.);
+                moment_y = moment_y - c.y * c.y * r * cos
+                    + 2. * c.y * r * r * change(&|t: f64| t / 2. - (2. * t).sin() / 4.)
+                    + r * r * r * change(&|t: f64| t.cos().powi(3) / 3. - t.cos());
+            },
+        }
+    }
+    let perimeter = segments.iter().map(|segment| segment.length()).fold(0., |sum, x| sum + x);
+    if area2.abs() <= 1e-12 * perimeter * perimeter {
+        let n = cmp::max(segments.len(), 1) as f64;
+        let (sum_x, sum_y) = segments.iter().map(|segment| segment.start_point())
+            .fold((0., 0.), |(x, y), p| (x + p.x, y + p.y));
+        return Measures { area: 0., perimeter: perimeter, centroid: Point::new(sum_x / n, sum_y / n) };
+    }
+    Measures {
+        area: (area2 / 2.).abs(),
+        perimeter: perimeter,
+        centroid: Point::new(moment_x / area2, moment_y / area2),
+    }
+}
+
+/// The measures of the polygon going through points in the order of indices.
+pub fn polygon_measures(points: &Vec<Point>, indices: &Vec<Index>) -> Measures {
+    let n = indices.len();
+    let sides = (0..n)
+        .map(|i| Segment::Line{ from: points[indices[i]], to: points[indices[(i + 1) % n]] })
+        .collect();
+    measures(&sides)
+}
+#[test]
+fn test_measures_square() {
+    use na;
+    let points = vec![Point::new(0., 0.), Point::new(0., 1.),
+                      Point::new(1., 1.), Point::new(1., 0.)];
+    let blob = Blob{ points: &points, hull: vec![0, 1, 2, 3], inblob: vec![true; 4],
+                     radii: vec![0.1; 4] };
+    // The square, a strip along each side, and a quarter circle on each corner.
+    let rounded = measures(&outline(&blob));
+    assert!(na::approx_eq(&rounded.area, &(1. + 4. * 0.1 + TAU / 2. * 0.01)));
+    assert!(na::approx_eq(&rounded.perimeter, &(4. + TAU * 0.1)));
+    assert!(na::approx_eq(&rounded.centroid, &Point::new(0.5, 0.5)));
+
+    let square = polygon_measures(&points, &blob.hull);
+    assert_eq!(square, Measures{ area: 1., perimeter: 4., centroid: Point::new(0.5, 0.5) });
+}
+#[test]
+fn test_measures_without_area() {
+    let points = vec![Point::new(1., 2.), Point::new(3., 2.), Point::new(2., 2.)];
+    assert_eq!(polygon_measures(&points, &vec![0]),
+               Measures{ area: 0., perimeter: 0., centroid: Point::new(1., 2.) });
+    assert_eq!(polygon_measures(&points, &vec![0, 1]),
+               Measures{ area: 0., perimeter: 4., centroid: Point::new(2., 2.) });
+    let collinear = polygon_measures(&points, &vec![0, 2, 1]);
+    assert_eq!((collinear.area, collinear.perimeter), (0., 4.));
+    assert!(collinear.centroid.x.is_finite() && collinear.centroid.y.is_finite());
+    assert_eq!(measures(&vec![]).centroid, Point::new(0., 0.));
+}
+#[test]
+fn test_measures_match_polyline() {
+    use na::Norm;
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.5), Point::new(2., 0.),
+                      Point::new(1., 2.)];
+    let blob = Blob{ points: &points, hull: vec![0, 3, 2, 1],
+                     inblob: vec![true, false, true, true], radii: vec![0.1, 0.2, 0.15, 0.1] };
+    let exact = measures(&outline(&blob));
+    let polygon = polyline(&blob, 1e-7);
+    let indices: Vec<Index> = (0..polygon.len()).collect();
+    let approx = polygon_measures(&polygon, &indices);
+    assert!((exact.area - approx.area).abs() < 1e-6);
+    assert!((exact.perimeter - approx.perimeter).abs() < 1e-6);
+    assert!((exact.centroid - approx.centroid).norm() < 1e-6);
+}
+
+/// The outline of blob as a polygon, going clockwise, with every arc cut
+/// into chords that are less than tolerance from it.
+/// The tangent lines are already straight, so they're kept as they are.
+/// A tolerance of 0 or less can't be met, so arcs get the shortest chords
+/// allowed instead, MIN_STEP round.
+pub fn polyline(blob: &Blob, tolerance: f64) -> Vec<Point> {
+    use na;
+    let mut polygon = vec![];
+    for segment in outline(blob) {
+        if let Segment::Arc{ centre, radius, start, end, .. } = segment {
+            flatten_arc(&mut polygon, centre, radius, start, end, tolerance);
+        }
+    }
+    // The polygon closes itself, so a last point back at the start is extra.
+    let n = polygon.len();
+    if n > 1 && na::approx_eq(&polygon[0], &polygon[n - 1]) {
+        polygon.pop();
+    }
+    if signed_area(&polygon) > 0. {
+        polygon.reverse();
+    }
+    polygon
+}
+
+// The shortest chord an arc gets cut into, as an angle,
+// so tiny tolerances don't make endless points.
+const MIN_STEP: f64 = TAU / 4096.;
+
+/// Adds the points along an arc to polygon, close enough together
+/// that the chords between them stay within tolerance of it.
+fn flatten_arc(polygon: &mut Vec<Point>, centre: Point, radius: Radius,
+               start: f64, end: f64, tolerance: f64) {
+    use na;
+    // A chord of angle t is at most radius * (1 - cos(t/2)) from the arc.
+    // Past a quarter turn the chords stop looking like the circle at all.
+    // A tolerance of 0 or less, or NaN, can't be met, so take the shortest chords.
+    let max_step = if !(tolerance > 0.) {
+        MIN_STEP
+    } else if tolerance < radius {
+        (2. * (1. - tolerance / radius).acos()).min(TAU / 4.).max(MIN_STEP)
+    } else {
+        TAU / 4.
+    };
+    let sweep = end - start;
+    let pieces = ((sweep.abs() / max_step).ceil() as usize).max(1);
+    for piece in 0..pieces + 1 {
+        let p = point_at(centre, radius, start + sweep * piece as f64 / pieces as f64);
+        // Tangent lines can be empty, so don't repeat points.
+        if polygon.last().map_or(true, |last| !na::approx_eq(last, &p)) {
+            polygon.push(p);
+        }
+    }
+}
+#[test]
+fn test_flatten_arc_tolerance() {
+    let mut polygon = vec![];
+    flatten_arc(&mut polygon, Point::new(0., 0.), 1., 0., TAU, 0.01);
+    // 2 acos(0.99) is a bit over 16 degrees, so 23 pieces go round.
+    assert_eq!(polygon.len(), 24);
+    for i in 0..polygon.len() - 1 {
+        let (a, b) = (polygon[i], polygon[i + 1]);
+        let mid = Point::new((a.x + b.x) / 2., (a.y + b.y) / 2.);
+        let gap = 1. - (mid.x * mid.x + mid.y * mid.y).sqrt();
+        assert!(gap < 0.01, "Chord {} is {} from the circle", i, gap);
+    }
+}
+
+#[test]
+fn test_polyline_without_tolerance() {
+    use std::f64;
+    let points = vec![Point::new(0., 0.), Point::new(0., 1.),
+                      Point::new(1., 1.), Point::new(1., 0.)];
+    let blob = Blob{ points: &points, hull: vec![0, 1, 2, 3], inblob: vec![true; 4],
+                     radii: vec![0.1; 4] };
+    for &tolerance in &[0., -1., f64::NAN] {
+        // About a quarter of MIN_STEP's 4096 pieces round each corner.
+        let n = polyline(&blob, tolerance).len();
+        assert!(4 * 1024 <= n && n <= 4 * 1026, "{} points for tolerance {}", n, tolerance);
+    }
+}
+
+/// Twice the area of polygon, positive if it goes counter clockwise.
+fn signed_area(polygon: &Vec<Point>) -> f64 {
+    let n = polygon.len();
+    (0..n).map(|i| {
+        let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+        a.x * b.y - b.x * a.y
+    }).fold(0., |sum, x| sum + x)
+}
+
+#[test]
+fn test_polyline_separates_points() {
+    use blob::point_inside;
+    use na::{Dot, Norm};
+    // A dent in the bottom for point 1, with points inside and outside too.
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.5), Point::new(2., 0.),
+                      Point::new(1., 2.), Point::new(1., 1.), Point::new(1., -1.),
+                      Point::new(3., 3.)];
+    let blob = Blob{ points: &points, hull: vec![0, 3, 2, 1],
+                     inblob: vec![true, false, true, true, true, false, false],
+                     radii: vec![0.1, 0.1, 0.1, 0.1, 0.2, 0.3, 0.3] };
+    let polygon = polyline(&blob, 0.001);
+    assert!(signed_area(&polygon) < 0., "The polygon should go clockwise");
+    let corners: Vec<Index> = (0..polygon.len()).collect();
+
+    for (ix, p) in points.iter().enumerate() {
+        // Strictly, so not on an edge either.
+        let gap = (0..polygon.len()).map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            let ab = b - a;
+            let t = ((*p - a).dot(&ab) / ab.sqnorm()).max(0.).min(1.);
+            (*p - (a + ab * t)).norm()
+        }).fold(f64::INFINITY, |a, b| a.min(b));
+        assert!(gap > 0.05, "Point {} is only {} from the outline", ix, gap);
+        assert_eq!(point_inside(&polygon, p, &corners), blob.is_in(ix));
+    }
+}
+
+
+pub fn smooth_line_normal(&a: &Point, a_r: f64, a_inblob: bool,
+                          &b: &Point, b_r: f64, b_inblob: bool) -> Vector {
+    use na::*;
+    //                     ___
+    //        \n     ___---\ b_r
+    //  a      \__---       b
+    //r_a\__---blob
+    //
+    //                   __c
+    //            _____-- x\   x is 90 degrees.
+    //  z ____----         y\a_r+b_r == delta
+    // a------------d--------b
+    //
+    //          d   ___---b
+    //       ___----     y| a_r+b_r
+    //  __--z            x|
+    // a------------------c
+    // Form a triangle with ab as the hypotenuse,
+    // r1±r2 as the height, and the third side unknown
+    // delta / distance = sin(z) = cos(y)
+    let mut d:Vector = b - a;
+    let distance:f64 = d.normalize_mut();
+    // d is now normalized
+    let delta = if a_inblob == b_inblob {
+        (a_r - b_r)/distance
+    } else {
+        (a_r + b_r)/distance
+    };
+    assert!(delta <= 1., "The radii not must overlap! a_r:{}, b_r:{} distance:{}",
+           a_r, b_r, distance);
+
+    let mut y = delta.acos();
+    if !a_inblob && b_inblob {
+        y = (TAU/2.)-y
+    }
+    rotate_ccw(y, d)
+}
+
+
+#[test]
+fn test_smooth_line_normal_up() {
+    use na;
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(1.0, 0.0);
+
+    let up = smooth_line_normal(&a, 0.2, true, &b, 0.2, true);
+    println!("Up is {:?}", up);
+    assert!(na::approx_eq(&up, &Vector::new(0.0, 1.0)));
+}
+
+#[test]
+fn test_smooth_line_normal_up2() {
+    use na;
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(1.0, 0.0);
+
+    let up2 = smooth_line_normal(&a, 0.2, false, &b, 0.2, false);
+    println!("up2 is {:?}", up2);
+    assert!(na::approx_eq(&up2, &Vector::new(0.0,  1.0)));
+}
+
+#[test]
+fn test_smooth_line_normal_upish_leftish() {
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(1.0, 0.0);
+
+    let uppish_leftish = smooth_line_normal(&a, 0.2, true, &b, 0.8, true);
+    println!("Uppish leftish is {:?}", uppish_leftish);
+    assert!(uppish_leftish.x < 0.);
+    assert!(uppish_leftish.y > 0.);
+}
+
+#[test]
+fn test_smooth_line_normal_up_left() {
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(1.0, 0.0);
+    //           \         ____
+    //  o         \____---- o
+    //    ____---- blob
+    //
+    let up_left = smooth_line_normal(&a, 0.2, false, &b, 0.2, true);
+    println!("up_left is {:?}", up_left);
+    assert!(up_left.x < 0.);
+    assert!(up_left.y > 0.);
+}
+
+#[test]
+fn test_smooth_line_normal_up_right() {
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(1.0, 0.0);
+
+    let up_right = smooth_line_normal(&a, 0.2, true, &b, 0.2, false);
+    println!("up_right is {:?}", up_right);
+    assert!(up_right.x > 0.);
+    assert!(up_right.y > 0.);
+}
+#[test]
+fn test_smooth_line_normal_torus_bad() {
+    let a = Point::new(0.707,-0.707);
+    let b = Point::new(0.0, 0.0);
+
+    let n = smooth_line_normal(&a, 0.1, true, &b, 0.1, false);
+    println!("n: {:?}", n);
+    assert!(n.x < 0.);
+    assert!(n.y < 0.);
+    assert!(n.x < n.y); // Pointing more x-ly
+}
+
+
+/// Normalizes an angle to be within [0,2*PI)
+/// # Examples
+/// ```
+/// ```
+fn normalize_angle(mut a: f64) -> f64 {
+    while a < 0. { a = a + TAU; }
+    while a >= TAU { a = a - TAU; }
+    a
+}
+#[test]
+fn test_normalize_angle() {
+    use std::f64::consts::PI;
+
+    assert_eq!(normalize_angle(-PI), PI);
+    assert!(normalize_angle(-0.785398) > 0.);
+    assert_eq!(normalize_angle((-135.0 as f64).to_radians()), (225.0 as f64).to_radians());
+}
+
+
+pub fn smooth_line_angle(a: &Point, a_r: f64, a_inblob: bool,
+                         b: &Point, b_r: f64, b_inblob: bool) -> (f64,f64) {
+    let n = smooth_line_normal(a, a_r, a_inblob, b, b_r, b_inblob);
+    let theta = n.y.atan2(n.x); // Yes, I know it's strange.
+
+    let theta = normalize_angle(theta);
+
+    if a_inblob && b_inblob {
+        (theta, theta)
+    } else if !a_inblob && !b_inblob {
+        // The line passes under excluded points, so use the tangent
+        // on the other side, mirrored across ab.
+        let ab = (b.y - a.y).atan2(b.x - a.x);
+        let theta = normalize_angle(2. * ab - theta);
+        (theta, theta)
+    } else if a_inblob {
+        (theta, normalize_angle(theta + TAU/2.))
+    } else {
+        // Going into the blob, the normal belongs to b.
+        (normalize_angle(theta + TAU/2.), theta)
+    }
+}
+
+#[test]
+fn test_smooth_line_angle() {
+    let a = Point::new(0.,0.);
+    let points = vec![
+        Point::new(0., -1.),  // 0
+        Point::new(1., -1.),  // 1
+        Point::new(1.,  0.),  // 2
+        Point::new(1.,  1.),  // 3
+        Point::new(0.,  1.),  // 4
+        Point::new(-1., 1.),  // 5
+        Point::new(-1., 0.),  // 6
+        Point::new(-1., -1.),  // 7
+        ];
+
+    for (i, &b) in points.iter().enumerate() {
+        let angle = (i * 45) as f64;
+        let (theta, _theta2) = smooth_line_angle(&a, 0.1, true, &b, 0.1, true);
+        assert!((theta.to_degrees() - angle).abs() < 0.01,
+            "true true angles incorrect in {}, expected {}, got {}",
+            i, angle, theta.to_degrees());
+        // Excluded points get the tangent on the opposite side.
+        let angle = (angle + 180.) % 360.;
+        let (theta, _theta2) = smooth_line_angle(&a, 0.1, false, &b, 0.1, false);
+        assert!((theta.to_degrees() - angle).abs() < 0.01, "false false angles incorrect in {}", i);
+    }
+}
+
+#[test]
+fn test_smooth_line_angle_dent() {
+    // b is excluded, and the line should dip under it.
+    let a = Point::new(0.,0.);
+    let b = Point::new(1.,0.);
+    let c = Point::new(2.,0.);
+
+    let (a_ang, b_ang_in) = smooth_line_angle(&a, 0.1, true, &b, 0.1, false);
+    let (b_ang_out, c_ang) = smooth_line_angle(&b, 0.1, false, &c, 0.1, true);
+    assert!(a_ang.sin() > 0.);
+    assert!(b_ang_in.sin() < 0.);
+    assert!(b_ang_out.sin() < 0.);
+    assert!(c_ang.sin() > 0.);
+    // The arc around b is the short one, under it.
+    assert!(b_ang_in < b_ang_out);
+}
@@ -0,0 +1,195 @@
+use types::{Point, Index, cmp_f64};
+use blob::{giftwrap, orientation};
+use blob::Orientation::*;
+
+use std::cmp::Ordering;
+
+/// An algorithm for finding the convex hull of some of the points.
+/// So they are interchangeable, every algorithm returns the hull clockwise,
+/// ending with the leftmost (then lowest) point, and skips colinear points.
+pub trait ConvexHull {
+    fn hull(&self, points: &Vec<Point>, included: &Vec<Index>) -> Vec<Index>;
+}
+
+pub struct Giftwrap;
+pub struct MonotoneChain;
+pub struct QuickHull;
+
+/// Looks up an algorithm by the name used for `hull_algorithm` in the config.
+pub fn from_name(name: &str) -> Option<Box<ConvexHull>> {
+    match name {
+        "giftwrap" => Some(Box::new(Giftwrap)),
+        "monotone_chain" => Some(Box::new(MonotoneChain)),
+        "quickhull" => Some(Box::new(QuickHull)),
+        _ => None,
+    }
+}
+
+impl ConvexHull for Giftwrap {
+    fn hull(&self, points: &Vec<Point>, included: &Vec<Index>) -> Vec<Index> {
+        giftwrap(points, included)
+    }
+}
+
+/// Andrew's monotone chain algorithm.
+/// Runs in O(n log n), where n = included.len().
+impl ConvexHull for MonotoneChain {
+    fn hull(&self, points: &Vec<Point>, included: &Vec<Index>) -> Vec<Index> {
+        if included.len() <= 2 {
+            return included.clone();
+        }
+
+        let mut sorted = included.clone();
+        sorted.sort_by(|&a, &b| {
+            let (a, b) = (points[a], points[b]);
+            match cmp_f64(a.x, b.x) {
+                Ordering::Equal => cmp_f64(a.y, b.y),
+                other => other,
+            }
+        });
+
+        // Builds the chain counter clockwise, then flips it at the end.
+        let mut lower: Vec<Index> = vec![];
+        for &ix in &sorted {
+            push_turning_left(points, &mut lower, ix);
+        }
+        let mut upper: Vec<Index> = vec![];
+        for &ix in sorted.iter().rev() {
+            push_turning_left(points, &mut upper, ix);
+        }
+        // Each chain ends where the other one starts.
+        lower.pop();
+        upper.pop();
+
+        let mut hull = lower;
+        hull.extend(upper.into_iter());
+        // This started with the leftmost, so now it ends with it.
+        hull.reverse();
+        hull
+    }
+}
+
+fn push_turning_left(points: &Vec<Point>, chain: &mut Vec<Index>, ix: Index) {
+    while chain.len() >= 2 {
+        let a = points[chain[chain.len() - 2]];
+        let b = points[chain[chain.len() - 1]];
+        if orientation(a, b, points[ix]) == CounterClockwise {
+            break;
+        }
+        chain.pop();
+    }
+    chain.push(ix);
+}
+
+/// The QuickHull algorithm.
+/// Runs in O(n log n) on average, but O(n^2) in the worst case.
+impl ConvexHull for QuickHull {
+    fn hull(&self, points: &Vec<Point>, included: &Vec<Index>) -> Vec<Index> {
+        if included.len() <= 2 {
+            return included.clone();
+        }
+
+        let mut leftmost = included[0];
+        let mut rightmost = included[0];
+        for &ix in included {
+            let (p, l, r) = (points[ix], points[leftmost], points[rightmost]);
+            if (p.x, p.y) < (l.x, l.y) {
+                leftmost = ix;
+            }
+            if (p.x, p.y) > (r.x, r.y) {
+                rightmost = ix;
+            }
+        }
+
+        // Clockwise, so over the top from left to right, then back under.
+        let mut hull = vec![];
+        quickhull_side(points, included, leftmost, rightmost, &mut hull);
+        hull.push(rightmost);
+        quickhull_side(points, included, rightmost, leftmost, &mut hull);
+        hull.push(leftmost);
+        hull
+    }
+}
+
+/// Pushes the hull points strictly left of the line from a to b,
+/// in order from a to b.
+fn quickhull_side(points: &Vec<Point>, candidates: &Vec<Index>,
+                  a: Index, b: Index, hull: &mut Vec<Index>) {
+    let (pa, pb) = (points[a], points[b]);
+    let left: Vec<Index> = candidates.iter().cloned()
+        .filter(|&ix| orientation(pa, pb, points[ix]) == CounterClockwise)
+        .collect();
+
+    let mut farthest = match left.first() {
+        Some(&ix) => ix,
+        None => return,
+    };
+    let mut farthest_cross = 0.;
+    for &ix in &left {
+        let (ab, ap) = (pb - pa, points[ix] - pa);
+        let cross = ab.x * ap.y - ab.y * ap.x;
+        if cross > farthest_cross {
+            farthest = ix;
+            farthest_cross = cross;
+        }
+    }
+
+    quickhull_side(points, &left, a, farthest, hull);
+    hull.push(farthest);
+    quickhull_side(points, &left, farthest, b, hull);
+}
+
+#[cfg(test)]
+fn all_hulls(points: &Vec<Point>, included: &Vec<Index>) -> Vec<Vec<Index>> {
+    ["giftwrap", "monotone_chain", "quickhull"].iter()
+        .map(|name| from_name(name).unwrap().hull(points, included))
+        .collect()
+}
+
+#[test]
+fn test_hulls_square() {
+    let points = vec![
+        Point::new(0.0, 0.0), // 0
+        Point::new(1.0, 0.0), // 1
+        Point::new(1.0, 1.0), // 2
+        Point::new(0.0, 1.0), // 3
+        Point::new(0.5, 0.5), // 4
+        Point::new(0.5, 1.0), // 5
+    ];
+    let inpoints = vec![4,1,0,5,2,3];
+    for hull in all_hulls(&points, &inpoints) {
+        assert_eq!(hull, vec![ 3, 2, 1, 0 ]);
+    }
+}
+#[test]
+fn test_hulls_agree_r100() {
+    use input::{read_points, read_combs};
+
+    let points = read_points("datasets/r100.dat").unwrap();
+    let everything: Vec<Index> = (0..points.len()).collect();
+    let hulls = all_hulls(&points, &everything);
+    assert_eq!(hulls[0], hulls[1]);
+    assert_eq!(hulls[0], hulls[2]);
+
+    let combs = read_combs("datasets/merge.txt", points.len()).unwrap();
+    for inpoints in combs.iter().flat_map(|comb| comb.iter()) {
+        let hulls = all_hulls(&points, inpoints);
+        assert_eq!(hulls[0], hulls[1]);
+        assert_eq!(hulls[0], hulls[2]);
+    }
+}
+#[test]
+fn test_hulls_agree_line() {
+    use input::read_points;
+
+    let points = read_points("datasets/line").unwrap();
+    let everything: Vec<Index> = (0..points.len()).collect();
+    let hulls = all_hulls(&points, &everything);
+    assert_eq!(hulls[0], hulls[1]);
+    assert_eq!(hulls[0], hulls[2]);
+}
+#[test]
+fn test_from_name() {
+    assert!(from_name("quickhull").is_some());
+    assert!(from_name("bogosort").is_none());
+}
@@ -1,50 +1,454 @@
-
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
+use std::path::Path;
+use std::str::FromStr;
 
+use config::{Config, ConfigInput};
+use csv;
+use json;
 use types::{Point, Index, Comb};
 
-pub fn read_points(mut f:File) -> Vec<Point> {
-    let mut data_str = String::new();
-    f.read_to_string(&mut data_str).unwrap();
-
-    let data: Vec<&str> = data_str
-        .split(char::is_whitespace)
-        .skip(1).collect();
-    let points: Vec<Point> = data
-        .chunks(2)
-        .filter(|s| s.len() == 2)
-        .map(|s| {
-            Point::new(s[0].parse().unwrap(), s[1].parse().unwrap())
-        })
-        .collect();
+/// Points along with what they are called.
+pub struct LabelledPoints {
+    pub points: Vec<Point>,
+    /// What combs can call each point, or empty if they only use positions.
+    pub ids: Vec<String>,
+    /// What to write next to each point, or empty to number them round the hull.
+    pub labels: Vec<String>,
+}
+
+impl LabelledPoints {
+    pub fn unlabelled(points: Vec<Point>) -> LabelledPoints {
+        LabelledPoints {
+            points: points,
+            ids: vec![],
+            labels: vec![],
+        }
+    }
+}
+
+/// What went wrong reading an input file, and where.
+/// Lines and columns count from 1.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The file couldn't be read at all.
+    Io { file: String, error: io::Error },
+    /// Something other than what the format needs was found.
+    Syntax { file: String, line: usize, column: usize, expected: String, found: String },
+    /// A comb refers to a point that isn't in the points file.
+    BadIndex { file: String, line: usize, column: usize, index: Index, points: usize },
+    /// The header of a points file doesn't agree with the points in it.
+    Count { file: String, declared: usize, found: usize },
+    /// A JSON file is valid JSON, but not shaped the way it should be.
+    /// at is where in the document, like `[2][0]`.
+    Json { file: String, at: String, expected: String, found: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Io{ ref file, ref error } =>
+                write!(f, "{}: {}", file, error),
+            ParseError::Syntax{ ref file, line, column, ref expected, ref found } =>
+                write!(f, "{}:{}:{}: expected {}, found {}",
+                       file, line, column, expected, found),
+            ParseError::BadIndex{ ref file, line, column, index, points } =>
+                write!(f, "{}:{}:{}: there is no point {}, there are only {} points",
+                       file, line, column, index, points),
+            ParseError::Count{ ref file, declared, found } =>
+                write!(f, "{}: the header says there are {} points, but there are {}",
+                       file, declared, found),
+            ParseError::Json{ ref file, ref at, ref expected, ref found } =>
+                write!(f, "{}: at {}: expected {}, found {}",
+                       file, at, expected, found),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        match *self {
+            ParseError::Io{ .. } => "could not read input file",
+            ParseError::Syntax{ .. } => "syntax error in input file",
+            ParseError::BadIndex{ .. } => "point index out of range",
+            ParseError::Count{ .. } => "wrong number of points",
+            ParseError::Json{ .. } => "badly shaped JSON",
+        }
+    }
+}
 
-    points
+/// A whitespace separated word of an input file, and where it was.
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
 }
 
-pub fn read_combs(mut f:File) -> Vec<Comb> {
-    let mut data_str = String::new();
-    f.read_to_string(&mut data_str).unwrap();
+fn tokenize(line_num: usize, line: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    // The byte offset and column the current token started at.
+    let mut start: Option<(usize, usize)> = None;
+    for (column, (offset, c)) in line.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (true, Some((begin, begin_column))) => {
+                tokens.push(Token{ text: &line[begin..offset],
+                                   line: line_num, column: begin_column + 1 });
+                start = None;
+            },
+            (false, None) => start = Some((offset, column)),
+            _ => (),
+        }
+    }
+    if let Some((begin, begin_column)) = start {
+        tokens.push(Token{ text: &line[begin..], line: line_num, column: begin_column + 1 });
+    }
+    tokens
+}
+#[test]
+fn test_tokenize() {
+    let tokens = tokenize(3, "3  43 58 15 \r");
+    let found: Vec<(&str, usize)> = tokens.iter().map(|t| (t.text, t.column)).collect();
+    assert_eq!(found, vec![("3", 1), ("43", 4), ("58", 7), ("15", 10)]);
+    assert!(tokens.iter().all(|t| t.line == 3));
+}
+
+/// How an input is written.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// Our own whitespace separated numbers.
+    Plain,
+    /// JSON, or GeoJSON for points.
+    Json,
+    /// CSV with a header naming the columns, only for points.
+    Csv,
+}
+
+impl Format {
+    /// The format a file is in, going by its extension.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") | Some("geojson") => Format::Json,
+            Some("csv") => Format::Csv,
+            _ => Format::Plain,
+        }
+    }
+}
+
+fn parse_token<T: FromStr>(file: &str, token: &Token, expected: &str) -> Result<T, ParseError> {
+    token.text.parse().map_err(|_| ParseError::Syntax{
+        file: file.to_string(),
+        line: token.line,
+        column: token.column,
+        expected: expected.to_string(),
+        found: format!("\"{}\"", token.text),
+    })
+}
+
+/// Parses text, found at line and column of file, as a coordinate.
+/// It has to be a finite number, or the points can't be drawn.
+/// Every format that has coordinates as text reads them with this.
+pub fn parse_coordinate(file: &str, line: usize, column: usize, text: &str, expected: &str)
+        -> Result<f64, ParseError> {
+    match text.parse::<f64>() {
+        Ok(x) if x.is_finite() => Ok(x),
+        _ => Err(ParseError::Syntax{
+            file: file.to_string(), line: line, column: column,
+            expected: expected.to_string(), found: format!("\"{}\"", text),
+        }),
+    }
+}
+
+fn end_of_file(file: &str, contents: &str, expected: &str) -> ParseError {
+    ParseError::Syntax{
+        file: file.to_string(),
+        line: contents.lines().count() + 1,
+        column: 1,
+        expected: expected.to_string(),
+        found: "end of file".to_string(),
+    }
+}
+
+fn read_contents<R: Read>(mut reader: R, file: &str) -> Result<String, ParseError> {
+    let mut contents = String::new();
+    match reader.read_to_string(&mut contents) {
+        Ok(_) => Ok(contents),
+        Err(e) => Err(ParseError::Io{ file: file.to_string(), error: e }),
+    }
+}
+
+fn open(path: &Path) -> Result<File, ParseError> {
+    File::open(path).map_err(|e| ParseError::Io{ file: path.display().to_string(), error: e })
+}
+
+/// Reads the points in path.
+/// The file starts with the number of points, optionally followed by their
+/// dimension on the same line, which has to be 2.
+/// Points can also come in groups, with a line giving the size of each group,
+/// as in `datasets/circle`.
+/// After the header come the coordinates, separated by any whitespace.
+/// Files ending in .json or .geojson are read with json::parse_points instead.
+pub fn read_points<P: AsRef<Path>>(path: P) -> Result<Vec<Point>, ParseError> {
+    let path = path.as_ref();
+    let f = try!(open(path));
+    read_points_from(f, &path.display().to_string(), Format::from_path(path))
+}
 
-    let mut it = data_str.lines();
+/// Reads points in format from reader, as read_points does.
+/// File is what to call the reader in errors.
+/// CSV is read with the default column names.
+pub fn read_points_from<R: Read>(reader: R, file: &str, format: Format)
+        -> Result<Vec<Point>, ParseError> {
+    let contents = try!(read_contents(reader, file));
+    match format {
+        Format::Plain => parse_points(file, &contents),
+        Format::Json => json::parse_points(file, &contents),
+        Format::Csv => csv::parse_points(file, &contents, &Config::default().input)
+            .map(|labelled| labelled.points),
+    }
+}
+
+fn parse_points(file: &str, contents: &str) -> Result<Vec<Point>, ParseError> {
+    let mut lines = contents.lines().enumerate()
+        .map(|(i, line)| tokenize(i + 1, line))
+        .filter(|tokens| !tokens.is_empty())
+        .peekable();
+
+    let header = match lines.next() {
+        Some(tokens) => tokens,
+        None => return Err(end_of_file(file, contents, "the number of points")),
+    };
+    let mut declared: usize = try!(parse_token(file, &header[0], "the number of points"));
+    if header.len() > 1 {
+        let dimension: usize = try!(parse_token(file, &header[1], "the dimension"));
+        if dimension != 2 {
+            return Err(ParseError::Syntax{
+                file: file.to_string(), line: header[1].line, column: header[1].column,
+                expected: "dimension 2".to_string(), found: format!("dimension {}", dimension),
+            });
+        }
+    }
+    if header.len() > 2 {
+        return Err(ParseError::Syntax{
+            file: file.to_string(), line: header[2].line, column: header[2].column,
+            expected: "the end of the header".to_string(),
+            found: format!("\"{}\"", header[2].text),
+        });
+    }
+    // Lines with a single number are the sizes of the later groups.
+    while lines.peek().map_or(false, |tokens| tokens.len() == 1) {
+        let tokens = lines.next().unwrap();
+        let group: usize = try!(parse_token(file, &tokens[0], "the number of points in a group"));
+        declared = declared + group;
+    }
+
+    let tokens: Vec<Token> = lines.flat_map(|tokens| tokens.into_iter()).collect();
+    let mut points = Vec::with_capacity(declared);
+    for pair in tokens.chunks(2) {
+        if pair.len() < 2 {
+            return Err(end_of_file(file, contents, "a y coordinate"));
+        }
+        let (x, y) = (&pair[0], &pair[1]);
+        let x = try!(parse_coordinate(file, x.line, x.column, x.text, "an x coordinate"));
+        let y = try!(parse_coordinate(file, y.line, y.column, y.text, "a y coordinate"));
+        points.push(Point::new(x, y));
+    }
+    if points.len() != declared {
+        return Err(ParseError::Count{
+            file: file.to_string(), declared: declared, found: points.len(),
+        });
+    }
+    Ok(points)
+}
+#[test]
+fn test_parse_points_error() {
+    let err = parse_points("p", "2\n1.0 2.0\n3.0 x4\n").unwrap_err();
+    assert_eq!(err.to_string(), "p:3:5: expected a y coordinate, found \"x4\"");
+    let err = parse_points("p", "").unwrap_err();
+    assert_eq!(err.to_string(), "p:1:1: expected the number of points, found end of file");
+    let err = parse_points("p", "2\n1.0 2.0\n3.0\n").unwrap_err();
+    assert_eq!(err.to_string(), "p:4:1: expected a y coordinate, found end of file");
+    let err = parse_points("p", "2 3\n1.0 2.0 3.0\n").unwrap_err();
+    assert_eq!(err.to_string(), "p:1:3: expected dimension 2, found dimension 3");
+    let err = parse_points("p", "3\n1.0 2.0\n3.0 4.0\n").unwrap_err();
+    assert_eq!(err.to_string(), "p: the header says there are 3 points, but there are 2");
+    let err = parse_points("p", "2\n1.0 2.0\nNaN 4.0\n").unwrap_err();
+    assert_eq!(err.to_string(), "p:3:1: expected an x coordinate, found \"NaN\"");
+    let err = parse_points("p", "1\n1.0 inf\n").unwrap_err();
+    assert_eq!(err.to_string(), "p:2:5: expected a y coordinate, found \"inf\"");
+}
+#[test]
+fn test_read_points_groups() {
+    let points = read_points("datasets/circle").unwrap();
+    assert_eq!(points.len(), 8);
+    assert_eq!(points[0], Point::new(1.0, 0.0));
+    assert_eq!(points[4], Point::new(0.707, 0.707));
+
+    let points = parse_points("p", "2 2\n1.0 2.0\n3.0 4.0\n").unwrap();
+    assert_eq!(points, vec![Point::new(1.0, 2.0), Point::new(3.0, 4.0)]);
+}
+
+/// Reads the points in path, along with their ids and labels if it is a
+/// CSV file, which are found in the columns named in columns.
+pub fn read_labelled_points<P: AsRef<Path>>(path: P, columns: &ConfigInput)
+        -> Result<LabelledPoints, ParseError> {
+    let path = path.as_ref();
+    let f = try!(open(path));
+    read_labelled_points_from(f, &path.display().to_string(), Format::from_path(path), columns)
+}
+
+/// Reads points in format from reader, as read_labelled_points does.
+pub fn read_labelled_points_from<R: Read>(reader: R, file: &str, format: Format,
+                                          columns: &ConfigInput)
+        -> Result<LabelledPoints, ParseError> {
+    match format {
+        Format::Csv => {
+            let contents = try!(read_contents(reader, file));
+            csv::parse_points(file, &contents, columns)
+        },
+        _ => read_points_from(reader, file, format).map(LabelledPoints::unlabelled),
+    }
+}
+
+/// Reads the combs in path, checking they only use the first num_points points.
+/// Files ending in .json are read with json::parse_combs instead.
+pub fn read_combs<P: AsRef<Path>>(path: P, num_points: usize) -> Result<Vec<Comb>, ParseError> {
+    let path = path.as_ref();
+    let f = try!(open(path));
+    read_combs_from(f, &path.display().to_string(), Format::from_path(path), num_points)
+}
+
+/// Reads combs in format from reader, as read_combs does.
+/// There's no CSV format for combs, so that is read as plain.
+pub fn read_combs_from<R: Read>(reader: R, file: &str, format: Format, num_points: usize)
+        -> Result<Vec<Comb>, ParseError> {
+    read_combs_with_ids(reader, file, format, num_points, &HashMap::new())
+}
+
+/// Reads the combs in path, where points can be given by their ids as well
+/// as their positions. An id that looks like a position means the id.
+pub fn read_labelled_combs<P: AsRef<Path>>(path: P, points: &LabelledPoints)
+        -> Result<Vec<Comb>, ParseError> {
+    let path = path.as_ref();
+    let f = try!(open(path));
+    read_labelled_combs_from(f, &path.display().to_string(), Format::from_path(path), points)
+}
+
+/// Reads combs in format from reader, as read_labelled_combs does.
+pub fn read_labelled_combs_from<R: Read>(reader: R, file: &str, format: Format,
+                                         points: &LabelledPoints)
+        -> Result<Vec<Comb>, ParseError> {
+    let ids = points.ids.iter().enumerate()
+        .map(|(ix, id)| (id.clone(), ix))
+        .collect();
+    read_combs_with_ids(reader, file, format, points.points.len(), &ids)
+}
+
+fn read_combs_with_ids<R: Read>(reader: R, file: &str, format: Format, num_points: usize,
+                                ids: &HashMap<String, Index>)
+        -> Result<Vec<Comb>, ParseError> {
+    let contents = try!(read_contents(reader, file));
+    match format {
+        Format::Json => json::parse_combs(file, &contents, num_points, ids),
+        _ => parse_combs(file, &contents, num_points, ids),
+    }
+}
+
+fn parse_combs(file: &str, contents: &str, num_points: usize, ids: &HashMap<String, Index>)
+        -> Result<Vec<Comb>, ParseError> {
+    let expected = if ids.is_empty() { "a point index" } else { "a point id or index" };
+    let mut lines = contents.lines().enumerate()
+        .map(|(i, line)| (i + 1, tokenize(i + 1, line)));
     let mut combs: Vec<Comb> = Vec::new();
     loop {
-        let comb_size: i32 = match it.next() {
+        // Blank lines between combs don't matter.
+        let header = match lines.find(|&(_, ref tokens)| !tokens.is_empty()) {
             None => break,
-            Some(comb_size_str) => comb_size_str.trim().parse().unwrap()
+            Some((_, tokens)) => tokens,
         };
+        let comb_size: usize = try!(parse_token(file, &header[0], "the number of sets in a comb"));
+
         let mut sets: Vec<Vec<Index>> = Vec::new(); // Also Comb
         for _ in 0..comb_size {
-            let line = it.next().expect("Comb cut short");
+            let (line_num, tokens) = match lines.next() {
+                None => return Err(end_of_file(file, contents, "a set")),
+                Some(line) => line,
+            };
+            if tokens.is_empty() {
+                return Err(ParseError::Syntax{
+                    file: file.to_string(), line: line_num, column: 1,
+                    expected: "a set".to_string(), found: "a blank line".to_string(),
+                });
+            }
+            let set_size: usize = try!(parse_token(file, &tokens[0], "the size of a set"));
+            if set_size != tokens.len() - 1 {
+                return Err(ParseError::Syntax{
+                    file: file.to_string(), line: line_num, column: tokens[0].column,
+                    expected: format!("{} points in the set", set_size),
+                    found: format!("{}", tokens.len() - 1),
+                });
+            }
 
-            let set: Vec<Index> = line.split(char::is_whitespace).skip(2) // Dropping the size
-                .map(|s| s.parse().unwrap())
-                .collect();
+            let mut set: Vec<Index> = Vec::with_capacity(tokens.len() - 1);
+            for token in &tokens[1..] {
+                if let Some(&index) = ids.get(token.text) {
+                    set.push(index);
+                    continue;
+                }
+                let index: Index = try!(parse_token(file, token, expected));
+                if index >= num_points {
+                    return Err(ParseError::BadIndex{
+                        file: file.to_string(), line: token.line, column: token.column,
+                        index: index, points: num_points,
+                    });
+                }
+                set.push(index);
+            }
             sets.push(set);
         }
-        let _ = it.next(); // Dropping the extra value
+        let _ = lines.next(); // Dropping the extra value
         combs.push(sets);
-
     }
-    combs
+    Ok(combs)
+}
+#[test]
+fn test_read_combs() {
+    let combs = read_combs("datasets/merge.txt", 100).unwrap();
+    assert_eq!(combs[0], vec![vec![43, 58, 15], vec![52, 43], vec![58, 27], vec![15, 80]]);
+
+    let err = read_combs("datasets/merge.txt", 50).unwrap_err();
+    assert_eq!(err.to_string(),
+               "datasets/merge.txt:2:7: there is no point 58, there are only 50 points");
+}
+#[test]
+fn test_parse_combs_cut_short() {
+    let no_ids = HashMap::new();
+    let err = parse_combs("c", "2\n1  0\n", 10, &no_ids).unwrap_err();
+    assert_eq!(err.to_string(), "c:3:1: expected a set, found end of file");
+    let err = parse_combs("c", "1\n2  0 1,\n", 10, &no_ids).unwrap_err();
+    assert_eq!(err.to_string(), "c:2:6: expected a point index, found \"1,\"");
+    let err = parse_combs("c", "1\n3  0 1\n", 10, &no_ids).unwrap_err();
+    assert_eq!(err.to_string(), "c:2:1: expected 3 points in the set, found 2");
+}
+#[test]
+fn test_read_labelled_combs() {
+    let points = read_labelled_points("datasets/square.csv", &Config::default().input).unwrap();
+    assert_eq!(points.ids, vec!["a", "b", "c", "d", "middle"]);
+    let combs = read_labelled_combs("datasets/square_combs.txt", &points).unwrap();
+    assert_eq!(combs, vec![vec![vec![0, 1, 4], vec![2, 3]]]);
+}
+#[test]
+fn test_read_from_memory() {
+    let points = read_labelled_points_from(&b"id,x,y\nfirst,0,0\nsecond,1,2\n"[..], "mem",
+                                           Format::Csv, &Config::default().input).unwrap();
+    assert_eq!(points.points, vec![Point::new(0., 0.), Point::new(1., 2.)]);
+    let combs = read_labelled_combs_from(&b"[[[\"second\", 0]]]"[..], "mem",
+                                         Format::Json, &points).unwrap();
+    assert_eq!(combs, vec![vec![vec![1, 0]]]);
+
+    let err = read_points_from(&b"3\n0 0\n1 1\n"[..], "mem", Format::Plain).unwrap_err();
+    assert_eq!(err.to_string(), "mem: the header says there are 3 points, but there are 2");
 }
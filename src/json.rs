@@ -0,0 +1,263 @@
+//! Reading and writing JSON, for tools that don't speak our whitespace
+//! formats, and GeoJSON, for GIS tools.
+
+use rustc_serialize::json;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::{BTreeMap, HashMap};
+
+use blob::Blob;
+use input::ParseError;
+use types::{Point, Index, Comb};
+
+fn parse(file: &str, contents: &str) -> Result<Json, ParseError> {
+    Json::from_str(contents).map_err(|e| match e {
+        json::ParserError::SyntaxError(code, line, column) => ParseError::Syntax{
+            file: file.to_string(), line: line, column: column,
+            expected: "valid JSON".to_string(),
+            found: json::error_str(code).to_string(),
+        },
+        json::ParserError::IoError(error) => ParseError::Io{
+            file: file.to_string(), error: error,
+        },
+    })
+}
+
+/// A short description of a value for error messages,
+/// without printing out whole arrays.
+fn describe(value: &Json) -> String {
+    match *value {
+        Json::Array(_) => "an array".to_string(),
+        Json::Object(_) => "an object".to_string(),
+        ref other => other.to_string(),
+    }
+}
+
+fn shape_error(file: &str, at: &str, expected: &str, found: String) -> ParseError {
+    ParseError::Json{
+        file: file.to_string(),
+        at: if at.is_empty() { "the top level".to_string() } else { at.to_string() },
+        expected: expected.to_string(),
+        found: found,
+    }
+}
+
+fn as_array<'a>(file: &str, at: &str, value: &'a Json, expected: &str)
+        -> Result<&'a Vec<Json>, ParseError> {
+    value.as_array().ok_or_else(|| shape_error(file, at, expected, describe(value)))
+}
+
+fn find<'a>(file: &str, at: &str, value: &'a Json, key: &str) -> Result<&'a Json, ParseError> {
+    value.find(key).ok_or_else(|| shape_error(file, at, &format!("a \"{}\" member", key),
+                                              describe(value)))
+}
+
+/// Reads a position, [x, y]. Any altitude after them is ignored.
+fn parse_point(file: &str, at: &str, value: &Json) -> Result<Point, ParseError> {
+    let coords = try!(as_array(file, at, value, "an [x, y] position"));
+    if coords.len() < 2 {
+        return Err(shape_error(file, at, "an [x, y] position",
+                               format!("{} coordinates", coords.len())));
+    }
+    let mut xy = [0.; 2];
+    for i in 0..2 {
+        let at = format!("{}[{}]", at, i);
+        xy[i] = match coords[i].as_f64() {
+            Some(x) if x.is_finite() => x,
+            // Numbers too big for an f64 come out as infinity.
+            Some(x) => return Err(shape_error(file, &at, "a finite coordinate", x.to_string())),
+            None => return Err(shape_error(file, &at, "a coordinate", describe(&coords[i]))),
+        };
+    }
+    Ok(Point::new(xy[0], xy[1]))
+}
+
+fn parse_positions(file: &str, at: &str, value: &Json) -> Result<Vec<Point>, ParseError> {
+    let positions = try!(as_array(file, at, value, "an array of positions"));
+    let mut points = Vec::with_capacity(positions.len());
+    for (i, position) in positions.iter().enumerate() {
+        points.push(try!(parse_point(file, &format!("{}[{}]", at, i), position)));
+    }
+    Ok(points)
+}
+
+/// Reads points from JSON, either as a plain array of [x, y] positions,
+/// or as GeoJSON: a MultiPoint, or a FeatureCollection of Points.
+pub fn parse_points(file: &str, contents: &str) -> Result<Vec<Point>, ParseError> {
+    let doc = try!(parse(file, contents));
+    match doc.find("type").and_then(|t| t.as_string()) {
+        None => parse_positions(file, "", &doc),
+        Some("MultiPoint") => {
+            let coords = try!(find(file, "", &doc, "coordinates"));
+            parse_positions(file, ".coordinates", coords)
+        },
+        Some("FeatureCollection") => {
+            let features = try!(find(file, "", &doc, "features"));
+            let features = try!(as_array(file, ".features", features, "an array of features"));
+            let mut points = Vec::with_capacity(features.len());
+            for (i, feature) in features.iter().enumerate() {
+                let at = format!(".features[{}]", i);
+                let geometry = try!(find(file, &at, feature, "geometry"));
+                let at = format!("{}.geometry", at);
+                match geometry.find("type").and_then(|t| t.as_string()) {
+                    Some("Point") => (),
+                    Some(other) => return Err(shape_error(file, &at, "a Point",
+                                                          format!("a {}", other))),
+                    None => return Err(shape_error(file, &at, "a Point",
+                                                   describe(geometry))),
+                }
+                let coords = try!(find(file, &at, geometry, "coordinates"));
+                let at = format!("{}.coordinates", at);
+                points.push(try!(parse_point(file, &at, coords)));
+            }
+            Ok(points)
+        },
+        Some(other) => Err(shape_error(file, ".type", "MultiPoint or FeatureCollection",
+                                       format!("\"{}\"", other))),
+    }
+}
+#[test]
+fn test_parse_points() {
+    let points = parse_points("p", "[[1, 2], [3.5, -4]]").unwrap();
+    assert_eq!(points, vec![Point::new(1., 2.), Point::new(3.5, -4.)]);
+
+    let geojson = r#"{"type": "FeatureCollection", "features": [
+        {"type": "Feature", "properties": {},
+         "geometry": {"type": "Point", "coordinates": [1, 2, 100]}}]}"#;
+    assert_eq!(parse_points("p", geojson).unwrap(), vec![Point::new(1., 2.)]);
+
+    let geojson = r#"{"type": "MultiPoint", "coordinates": [[1, 2], [3, 4]]}"#;
+    assert_eq!(parse_points("p", geojson).unwrap().len(), 2);
+}
+#[test]
+fn test_parse_points_errors() {
+    let err = parse_points("p", "[[1, 2], [3, \"a\"]]").unwrap_err();
+    assert_eq!(err.to_string(), "p: at [1][1]: expected a coordinate, found \"a\"");
+    let err = parse_points("p", "[[1, 2], [3, 1e400]]").unwrap_err();
+    assert_eq!(err.to_string(), "p: at [1][1]: expected a finite coordinate, found inf");
+    let err = parse_points("p", "[[1, 2], [3]]").unwrap_err();
+    assert_eq!(err.to_string(), "p: at [1]: expected an [x, y] position, found 1 coordinates");
+    let err = parse_points("p", "{}").unwrap_err();
+    assert_eq!(err.to_string(), "p: at the top level: expected an array of positions, found an object");
+    match parse_points("p", "[[1, 2],\n [3, 4]") {
+        Err(ParseError::Syntax{ line: 2, .. }) => (),
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+}
+
+/// Reads combs from JSON, as an array of combs,
+/// each an array of sets, each an array of points.
+/// Points are given by their position, or as a string, by their id.
+pub fn parse_combs(file: &str, contents: &str, num_points: usize, ids: &HashMap<String, Index>)
+        -> Result<Vec<Comb>, ParseError> {
+    let doc = try!(parse(file, contents));
+    let mut combs = vec![];
+    for (i, comb) in try!(as_array(file, "", &doc, "an array of combs")).iter().enumerate() {
+        let at = format!("[{}]", i);
+        let mut sets = vec![];
+        for (j, set) in try!(as_array(file, &at, comb, "an array of sets")).iter().enumerate() {
+            let at = format!("[{}][{}]", i, j);
+            let mut indices = vec![];
+            for (k, index) in try!(as_array(file, &at, set, "an array of point indices"))
+                    .iter().enumerate() {
+                let at = format!("[{}][{}][{}]", i, j, k);
+                match *index {
+                    Json::String(ref id) => match ids.get(id) {
+                        Some(&ix) => indices.push(ix),
+                        None => return Err(shape_error(file, &at, "a point id",
+                                                       describe(index))),
+                    },
+                    _ => match index.as_u64() {
+                        Some(ix) if (ix as usize) < num_points => indices.push(ix as Index),
+                        _ => return Err(shape_error(
+                            file, &at, &format!("a point index below {}", num_points),
+                            describe(index))),
+                    },
+                }
+            }
+            sets.push(indices);
+        }
+        combs.push(sets);
+    }
+    Ok(combs)
+}
+#[test]
+fn test_parse_combs() {
+    let mut ids = HashMap::new();
+    ids.insert("last".to_string(), 3);
+    let combs = parse_combs("c", "[[[0, 1], [2]], [[\"last\"]]]", 4, &ids).unwrap();
+    assert_eq!(combs, vec![vec![vec![0, 1], vec![2]], vec![vec![3]]]);
+
+    let err = parse_combs("c", "[[[0, 1], [2]], [[4]]]", 4, &ids).unwrap_err();
+    assert_eq!(err.to_string(), "c: at [1][0][0]: expected a point index below 4, found 4");
+    let err = parse_combs("c", "[[[\"first\"]]]", 4, &ids).unwrap_err();
+    assert_eq!(err.to_string(), "c: at [0][0][0]: expected a point id, found \"first\"");
+}
+
+/// A JSON object with the given keys and values.
+pub fn object(pairs: Vec<(&str, Json)>) -> Json {
+    let mut object = BTreeMap::new();
+    for (key, value) in pairs {
+        object.insert(key.to_string(), value);
+    }
+    Json::Object(object)
+}
+
+/// The blob computed for one set of a comb.
+/// The hull is indices into the points, in order round the outline,
+/// and radii and inblob have an entry for every point.
+pub fn blob_json(comb: usize, set: usize, blob: &Blob) -> Json {
+    object(vec![("comb", comb.to_json()), ("set", set.to_json()),
+                ("hull", blob.hull.to_json()), ("radii", blob.radii.to_json()),
+                ("inblob", blob.inblob.to_json())])
+}
+
+impl<'a> Blob<'a> {
+    /// The hull, radii and membership, for the comb-th comb's set-th set.
+    pub fn to_json(&self, comb: usize, set: usize) -> Json {
+        blob_json(comb, set, self)
+    }
+}
+#[test]
+fn test_blob_json() {
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.), Point::new(0., 1.)];
+    let blob = Blob{ points: &points, hull: vec![1, 0], inblob: vec![true, true, false],
+                     radii: vec![0.5, 0.25, 1.] };
+    assert_eq!(blob_json(2, 3, &blob).to_string(),
+               "{\"comb\":2,\"hull\":[1,0],\"inblob\":[true,true,false],\
+                \"radii\":[0.5,0.25,1.0],\"set\":3}");
+    assert_eq!(blob.to_json(2, 3), blob_json(2, 3, &blob));
+}
+
+/// A GeoJSON Feature with the outline of one blob as a Polygon.
+/// GeoJSON wants rings closed and going counter clockwise,
+/// so the clockwise outline is reversed, and the first point repeated.
+pub fn outline_feature(comb: usize, set: usize, outline: &Vec<Point>) -> Json {
+    let mut ring: Vec<Json> = outline.iter().rev()
+        .map(|p| vec![p.x, p.y].to_json())
+        .collect();
+    if let Some(first) = ring.first().cloned() {
+        ring.push(first);
+    }
+
+    let geometry = object(vec![("type", "Polygon".to_json()),
+                               ("coordinates", Json::Array(vec![Json::Array(ring)]))]);
+    let properties = object(vec![("comb", comb.to_json()), ("set", set.to_json())]);
+    object(vec![("type", "Feature".to_json()), ("geometry", geometry),
+                ("properties", properties)])
+}
+
+pub fn feature_collection(features: Vec<Json>) -> Json {
+    object(vec![("type", "FeatureCollection".to_json()), ("features", Json::Array(features))])
+}
+#[test]
+fn test_outline_feature() {
+    let outline = vec![Point::new(0., 0.), Point::new(0., 1.), Point::new(1., 0.)];
+    let feature = outline_feature(0, 1, &outline);
+    let ring = parse_positions("f", "", &feature.find_path(&["geometry", "coordinates"])
+                                                .unwrap().as_array().unwrap()[0]).unwrap();
+    assert_eq!(ring, vec![Point::new(1., 0.), Point::new(0., 1.),
+                          Point::new(0., 0.), Point::new(1., 0.)]);
+
+    let text = feature_collection(vec![]).to_string();
+    assert_eq!(text, "{\"features\":[],\"type\":\"FeatureCollection\"}");
+}
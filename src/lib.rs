@@ -12,9 +12,15 @@ extern crate nalgebra as na;
 
 pub mod blob;
 pub mod config;
+pub mod csv;
 pub mod draw;
+pub mod geometry;
+pub mod hull;
 pub mod input;
+pub mod json;
+pub mod pool;
+pub mod predicates;
+pub mod spatial;
 pub mod types;
-
-pub const EPSILON: f64 = 0.01;
+pub mod verify;
 
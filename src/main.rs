@@ -1,81 +1,278 @@
 extern crate rust_blob;
 #[macro_use] extern crate log;
 extern crate env_logger;
+extern crate rustc_serialize;
 
 
-use rust_blob::config::{parse_config, parse_args};
-use rust_blob::input::{read_points, read_combs};
-use rust_blob::types::Color;
+use rust_blob::config::{Args, Config, load_valid_config, parse_args};
+use rust_blob::input::{LabelledPoints, read_labelled_points, read_labelled_combs};
+use rust_blob::types::{Index, Radius};
 use rust_blob::blob;
+use rust_blob::json;
 use rust_blob::draw;
+use rust_blob::draw::pdf::{PdfDocument, PdfCanvas};
+use rust_blob::pool;
+use rust_blob::verify;
 
+use rustc_serialize::json::{Json, ToJson};
+
+use env_logger::LogBuilder;
+use log::LogRecord;
+
+use std::cell::RefCell;
+use std::env;
+use std::fmt::Display;
+use std::fs;
 use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
 
 
+thread_local!(
+    // The comb and set being worked on, to tag log lines with.
+    static TASK: RefCell<String> = RefCell::new(String::new())
+);
+
+fn set_task(task: String) {
+    TASK.with(|t| *t.borrow_mut() = task);
+}
+
+/// Logs like env_logger does, but with the task each line came from.
+fn init_logging() {
+    let mut builder = LogBuilder::new();
+    builder.format(|record: &LogRecord| {
+        TASK.with(|task| {
+            let task = task.borrow();
+            if task.is_empty() {
+                format!("{}:{}: {}", record.level(), record.location().module_path(),
+                        record.args())
+            } else {
+                format!("{}:{}: [{}] {}", record.level(), record.location().module_path(),
+                        *task, record.args())
+            }
+        })
+    });
+    if let Ok(spec) = env::var("RUST_LOG") {
+        builder.parse(&spec);
+    }
+    builder.init().unwrap();
+}
+
 /// Documenation for main
 fn main() {
-    env_logger::init().unwrap();
+    init_logging();
     let args = parse_args();
     info!("{:?}", args);
+    if args.flag_jobs == 0 {
+        fail("--jobs needs to be at least 1");
+    }
 
-    let config = parse_config(File::open("config.toml").unwrap());
+    let config = load_valid_config(&args.flag_config, &args.flag_set)
+        .unwrap_or_else(|e| fail(e));
     info!("{:?}", config);
 
 
     info!("");
     info!("");
 
-    let points = read_points(File::open(args.arg_points_file).unwrap());
-    let combs = read_combs(File::open(args.arg_combs_file).unwrap());
-
-
-    let hull_color = Color::from_hex("aaaa00");
-    let inpoints_color = Color::from_hex("ff3333");
-    let expoints_color = Color::from_hex("0033bb");
-
-    for (comb_num, comb) in combs.iter().enumerate() {
-        for (set_num, set) in comb.iter().enumerate() {
-            use std::path::PathBuf;
-            use std::fs;
-            let filepath:PathBuf;
-            if args.flag_output_directories {
-                let filename = format!("{:02}/{:02}/{:02}.png",
-                                       args.arg_dest_prefix,
-                                       comb_num, set_num);
-                info!("Filename: {}", filename);
-                filepath = PathBuf::from(filename);
-                match filepath.parent() {
-                    Some(parent) => fs::create_dir_all(parent).unwrap(),
-                    _ => (),
-                }
-            } else {
-                let filename = format!("{:02}_{:02}_{:02}.png",
-                                       args.arg_dest_prefix,
-                                       comb_num, set_num);
-                filepath = PathBuf::from(filename);
+    let dataset = read_labelled_points(&args.arg_points_file, &config.input)
+        .unwrap_or_else(|e| fail(e));
+    let combs = read_labelled_combs(&args.arg_combs_file, &dataset)
+        .unwrap_or_else(|e| fail(e));
+    if args.cmd_verify {
+        let report = verify::verify(&config, &dataset.points, &combs)
+            .unwrap_or_else(|e| fail(e));
+        println!("{}", report.to_json().pretty());
+        process::exit(if report.is_ok() { 0 } else { 1 });
+    }
+    let nearest = blob::compute_nearest_distances(&dataset.points);
 
+    // Each image is a job, drawn with -c from a whole comb, otherwise from one set.
+    let mut jobs = vec![];
+    for (comb_num, comb) in combs.into_iter().enumerate() {
+        let sets: Vec<(usize, Vec<Index>)> = comb.into_iter().enumerate().collect();
+        if args.flag_combine_sets {
+            jobs.push(Job{ comb_num: comb_num, sets: sets });
+        } else {
+            for set in sets {
+                jobs.push(Job{ comb_num: comb_num, sets: vec![set] });
             }
-            // Compute the in and out pointsets
-            let inpoints = set;
-            let expoints = (0..points.len()).filter(
-                |ex| ! inpoints.iter().any(|inp| inp == ex)).collect();
-
-            let inblob = blob::make_inblob(points.len(), inpoints);
-            // Compute the polygon, and radii.
-            let (hull, radii) = blob::find_hull(
-                &config, &points, &inblob, &inpoints, &expoints );
-
-            info!("Hull:");
-            for &ix in &hull {
-                info!("({:6},{:6}) ", points[ix].x, points[ix].y);
-            }
-            // Draw it!
-            draw::draw( &config, &points,
-                        &hull, &hull_color,
-                        &inpoints, &inpoints_color,
-                        &expoints, &expoints_color,
-                        &inblob, &radii,
-                        filepath.as_path() );
         }
     }
+
+    // A pdf gets one page per image, all in the one document.
+    let mut document = if config.img.format == "pdf" {
+        Some(PdfDocument::new(config.img.width, config.img.height))
+    } else {
+        None
+    };
+
+    let shared = Arc::new(Shared{ args: args, config: config, dataset: dataset, nearest: nearest });
+    let finished = {
+        let (num_jobs, shared) = (shared.args.flag_jobs, shared.clone());
+        pool::map(num_jobs, jobs, move |job| run_job(&shared, job))
+    };
+    let args = &shared.args;
+
+    // Everything is written in comb and set order, however the jobs finished.
+    let mut results = vec![];
+    let mut features = vec![];
+    if args.flag_measures {
+        println!("comb\tset\tarea\tperimeter\tcentroid_x\tcentroid_y\t\
+                  hull_area\thull_perimeter\thull_centroid_x\thull_centroid_y");
+    }
+    for done in finished {
+        for line in &done.measures {
+            println!("{}", line);
+        }
+        results.extend(done.results.into_iter());
+        features.extend(done.features.into_iter());
+        if let (Some(doc), Some(page)) = (document.as_mut(), done.page) {
+            doc.add_page(page);
+        }
+    }
+
+    if let Some(doc) = document {
+        let filename = format!("{}.pdf", args.arg_dest_prefix);
+        info!("Filename: {}", filename);
+        let mut f = File::create(filename).unwrap();
+        doc.write_to(&mut f).unwrap();
+    }
+    if args.flag_json {
+        let filename = format!("{}.json", args.arg_dest_prefix);
+        info!("Filename: {}", filename);
+        let mut f = File::create(filename).unwrap();
+        write!(f, "{}\n", Json::Array(results).pretty()).unwrap();
+    }
+    if args.flag_geojson {
+        let filename = format!("{}.geojson", args.arg_dest_prefix);
+        info!("Filename: {}", filename);
+        let mut f = File::create(filename).unwrap();
+        write!(f, "{}\n", json::feature_collection(features).pretty()).unwrap();
+    }
+}
+
+/// What every job reads, and nothing changes once the jobs start.
+struct Shared {
+    args: Args,
+    config: Config,
+    dataset: LabelledPoints,
+    nearest: Vec<Radius>,
+}
+
+/// The sets to draw in one image.
+struct Job {
+    comb_num: usize,
+    sets: Vec<(usize, Vec<Index>)>,
+}
+
+/// What a job leaves to be written once they're all done.
+struct Finished {
+    results: Vec<Json>,
+    features: Vec<Json>,
+    measures: Vec<String>,
+    page: Option<PdfCanvas>,
+}
+
+/// Finds the blobs for the sets of job, and draws them.
+fn run_job(shared: &Shared, job: Job) -> Finished {
+    let (args, config) = (&shared.args, &shared.config);
+    let points = &shared.dataset.points;
+    let comb_num = job.comb_num;
+
+    let mut finished = Finished{ results: vec![], features: vec![], measures: vec![],
+                                 page: None };
+    let mut blobs = vec![];
+    for &(set_num, ref set) in &job.sets {
+        set_task(format!("comb {} set {}", comb_num, set_num));
+        // Compute the polygon, and radii.
+        let blob = blob::find_hull(config, points, set, &shared.nearest)
+            .unwrap_or_else(|e| fail(e));
+
+        info!("Hull:");
+        for &ix in &blob.hull {
+            info!("({:6},{:6}) ", points[ix].x, points[ix].y);
+        }
+
+        if args.flag_json {
+            finished.results.push(blob.to_json(comb_num, set_num));
+        }
+        if args.flag_measures {
+            let algorithm = config.hull_algorithm().unwrap_or_else(|e| fail(e));
+            let (shape, hull) = (blob.measures(), blob.hull_measures(&*algorithm));
+            finished.measures.push(format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", comb_num, set_num,
+                shape.area, shape.perimeter, shape.centroid.x, shape.centroid.y,
+                hull.area, hull.perimeter, hull.centroid.x, hull.centroid.y));
+        }
+        if args.flag_geojson {
+            let outline = blob.outline(blob.smallest_radius() * OUTLINE_TOLERANCE);
+            finished.features.push(json::outline_feature(comb_num, set_num, &outline));
+        }
+        blobs.push(blob);
+    }
+    // Each set of a combined comb gets its own colour.
+    let layers: Vec<draw::Layer> = blobs.iter().zip(job.sets.iter())
+        .map(|(blob, &(set_num, _))| {
+            let color = if args.flag_combine_sets { set_num } else { 0 };
+            draw::Layer{ blob: blob, color: draw::layer_color(color) }
+        })
+        .collect();
+
+    // Draw it!
+    let filename = if args.flag_combine_sets {
+        set_task(format!("comb {}", comb_num));
+        if args.flag_output_directories {
+            format!("{:02}/{:02}.{}", args.arg_dest_prefix,
+                    comb_num, config.img.format)
+        } else {
+            format!("{:02}_{:02}.{}", args.arg_dest_prefix,
+                    comb_num, config.img.format)
+        }
+    } else {
+        let set_num = job.sets[0].0;
+        if args.flag_output_directories {
+            format!("{:02}/{:02}/{:02}.{}", args.arg_dest_prefix,
+                    comb_num, set_num, config.img.format)
+        } else {
+            format!("{:02}_{:02}_{:02}.{}", args.arg_dest_prefix,
+                    comb_num, set_num, config.img.format)
+        }
+    };
+    finished.page = output(config, &shared.dataset, &layers, filename);
+    set_task(String::new());
+    finished
+}
+
+// GeoJSON outlines stay within this fraction of the smallest radius on the hull.
+const OUTLINE_TOLERANCE: f64 = 0.01;
+
+/// Reports an error that stops us from going any further.
+fn fail<E: Display>(error: E) -> ! {
+    let _ = writeln!(io::stderr(), "{}", error);
+    process::exit(1);
+}
+
+/// Draws the layers to filename, or for a pdf, onto a page to be added
+/// to the document later.
+fn output(config: &Config,
+          dataset: &LabelledPoints,
+          layers: &Vec<draw::Layer>,
+          filename: String) -> Option<PdfCanvas> {
+    let points = &dataset.points;
+    if config.img.format == "pdf" {
+        return Some(draw::render_page(config, points, &dataset.labels, layers));
+    }
+    info!("Filename: {}", filename);
+    let filepath = PathBuf::from(filename);
+    match filepath.parent() {
+        Some(parent) => fs::create_dir_all(parent).unwrap(),
+        _ => (),
+    }
+    draw::draw(config, points, &dataset.labels, layers, filepath.as_path())
+        .unwrap_or_else(|e| fail(format!("{}: {}", filepath.display(), e)));
+    None
 }
@@ -0,0 +1,61 @@
+//! Running independent jobs on a few threads at once.
+
+use std::cmp;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+
+/// Calls work on every task, using up to jobs threads,
+/// and gives back the results in the same order as the tasks.
+/// With a single job everything runs on this thread.
+pub fn map<T, R, F>(jobs: usize, tasks: Vec<T>, work: F) -> Vec<R>
+        where T: Send + 'static,
+              R: Send + 'static,
+              F: Fn(T) -> R + Send + Sync + 'static {
+    let count = tasks.len();
+    let threads = cmp::min(jobs, count);
+    if threads <= 1 {
+        return tasks.into_iter().map(|task| work(task)).collect();
+    }
+
+    let queue = Arc::new(Mutex::new(tasks.into_iter().enumerate()));
+    let work = Arc::new(work);
+    let (tx, rx) = mpsc::channel();
+    let mut workers = vec![];
+    for n in 0..threads {
+        let (queue, work, tx) = (queue.clone(), work.clone(), tx.clone());
+        workers.push(thread::Builder::new().name(format!("worker {}", n)).spawn(move || {
+            loop {
+                let next = queue.lock().unwrap().next();
+                match next {
+                    Some((i, task)) => tx.send((i, work(task))).unwrap(),
+                    None => break,
+                }
+            }
+        }).unwrap());
+    }
+    // Only the workers hold senders now, so rx ends when they're all done.
+    drop(tx);
+
+    let mut results: Vec<Option<R>> = (0..count).map(|_| None).collect();
+    for (i, result) in rx.iter() {
+        results[i] = Some(result);
+    }
+    for worker in workers {
+        if worker.join().is_err() {
+            panic!("A worker thread panicked");
+        }
+    }
+    results.into_iter().map(|result| result.unwrap()).collect()
+}
+#[test]
+fn test_map_keeps_order() {
+    let tasks: Vec<u64> = (0..50).collect();
+    let squares = map(4, tasks.clone(), |x| {
+        // Make the early tasks slow, so they finish out of order.
+        thread::sleep_ms(((50 - x) % 7) as u32);
+        x * x
+    });
+    assert_eq!(squares, tasks.iter().map(|x| x * x).collect::<Vec<u64>>());
+    assert_eq!(map(1, vec![1, 2], |x| x + 1), vec![2, 3]);
+    assert!(map(3, Vec::<u8>::new(), |x| x).is_empty());
+}
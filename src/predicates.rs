@@ -0,0 +1,127 @@
+//! Robust geometric predicates, after Shewchuk's
+//! "Adaptive Precision Floating-Point Arithmetic and Fast Robust
+//! Geometric Predicates".
+//! Plain floating point gets the sign wrong for nearly colinear points,
+//! and a fixed tolerance only works at one scale.  These give the exact
+//! sign at any scale, and only pay for it when the answer is close.
+
+use types::Point;
+
+// Half an ulp of 1.0, the relative error of a single operation.
+const EPSILON: f64 = 1.1102230246251565e-16;
+// 2^27 + 1, for splitting a double into two 26 bit halves.
+const SPLITTER: f64 = 134217729.0;
+// If the approximate determinant is bigger than this times the size of its
+// terms, its sign is right.
+const CCW_ERRBOUND: f64 = (3.0 + 16.0 * EPSILON) * EPSILON;
+
+/// Twice the signed area of the triangle abc.
+/// Positive if a, b, c go counter clockwise, negative if clockwise,
+/// and exactly zero only if they are colinear.
+/// The sign is always exact, the magnitude is approximate.
+pub fn orient2d(a: Point, b: Point, c: Point) -> f64 {
+    let detleft = (a.x - c.x) * (b.y - c.y);
+    let detright = (a.y - c.y) * (b.x - c.x);
+    let det = detleft - detright;
+
+    let detsum = detleft.abs() + detright.abs();
+    if det.abs() >= CCW_ERRBOUND * detsum {
+        return det;
+    }
+    orient2d_exact(a, b, c)
+}
+
+/// Expands the determinant into six products, and adds them up without
+/// any rounding error.
+fn orient2d_exact(a: Point, b: Point, c: Point) -> f64 {
+    let products = [
+        two_product(a.x, b.y),
+        two_product(-a.x, c.y),
+        two_product(-c.x, b.y),
+        two_product(-a.y, b.x),
+        two_product(a.y, c.x),
+        two_product(c.y, b.x),
+    ];
+    let mut expansion = Vec::with_capacity(2 * products.len());
+    for &(high, low) in products.iter() {
+        grow_expansion(&mut expansion, low);
+        grow_expansion(&mut expansion, high);
+    }
+    // The components don't overlap and increase in magnitude,
+    // so the biggest one decides the sign.
+    match expansion.iter().rev().find(|&&x| x != 0.) {
+        Some(&x) => x,
+        None => 0.,
+    }
+}
+
+/// a + b, as a rounded sum and the error in it.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    let b_virtual = x - a;
+    let a_virtual = x - b_virtual;
+    let b_roundoff = b - b_virtual;
+    let a_roundoff = a - a_virtual;
+    (x, a_roundoff + b_roundoff)
+}
+
+fn split(a: f64) -> (f64, f64) {
+    let c = SPLITTER * a;
+    let a_big = c - a;
+    let a_high = c - a_big;
+    (a_high, a - a_high)
+}
+
+/// a * b, as a rounded product and the error in it.
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let x = a * b;
+    let (a_high, a_low) = split(a);
+    let (b_high, b_low) = split(b);
+    let err1 = x - (a_high * b_high);
+    let err2 = err1 - (a_low * b_high);
+    let err3 = err2 - (a_high * b_low);
+    (x, (a_low * b_low) - err3)
+}
+
+/// Adds b to the expansion e in place, keeping it exact.
+fn grow_expansion(e: &mut Vec<f64>, b: f64) {
+    let mut q = b;
+    for component in e.iter_mut() {
+        let (sum, err) = two_sum(q, *component);
+        *component = err;
+        q = sum;
+    }
+    e.push(q);
+}
+
+#[test]
+fn test_orient2d_simple() {
+    let a = Point::new(0.0, 0.0);
+    let b = Point::new(1.0, 0.0);
+    let c = Point::new(0.0, 1.0);
+    assert_eq!(orient2d(a, b, c), 1.0);
+    assert_eq!(orient2d(a, c, b), -1.0);
+    assert_eq!(orient2d(a, b, Point::new(2.0, 0.0)), 0.0);
+}
+#[test]
+fn test_orient2d_nearly_colinear() {
+    // Plain floating point rounds this to zero.
+    let a = Point::new(0.5, 0.5000000000000001);
+    let b = Point::new(12.0, 12.0);
+    let c = Point::new(24.0, 24.0);
+    assert!(orient2d(a, b, c) > 0.);
+    assert!(orient2d(b, a, c) < 0.);
+
+    let a = Point::new(0.5, 0.5);
+    assert_eq!(orient2d(a, b, c), 0.);
+}
+#[test]
+fn test_orient2d_scale() {
+    for &scale in &[1e-12, 1e-3, 1.0, 1e3, 1e12] {
+        let a = Point::new(0.1 * scale, 0.1 * scale);
+        let b = Point::new(0.7 * scale, 0.2 * scale);
+        let c = Point::new(0.3 * scale, 0.9 * scale);
+        assert!(orient2d(a, b, c) > 0., "Wrong at scale {}", scale);
+        assert!(orient2d(a, c, b) < 0., "Wrong at scale {}", scale);
+    }
+}
@@ -0,0 +1,117 @@
+use types::{Point, Index, cmp_f64};
+
+use na::Norm;
+
+/// A 2d tree over a set of points, for nearest neighbour queries.
+/// Building it is O(n log^2 n), and each query is O(log n) on average,
+/// rather than the O(n) of checking every point.
+pub struct KdTree<'a> {
+    points: &'a Vec<Point>,
+    // The indices of the points, arranged so that the middle of every
+    // range splits it in two, on x at even depths and on y at odd ones.
+    order: Vec<Index>,
+}
+
+impl<'a> KdTree<'a> {
+    /// Points with a NaN or infinite coordinate are left out,
+    /// as they can't be anyone's nearest neighbour.
+    pub fn new(points: &'a Vec<Point>) -> KdTree<'a> {
+        let mut order: Vec<Index> = (0..points.len())
+            .filter(|&ix| points[ix].x.is_finite() && points[ix].y.is_finite())
+            .collect();
+        build(points, &mut order[..], 0);
+        KdTree{ points: points, order: order }
+    }
+
+    /// Finds the point closest to p, ignoring the point `skip`.
+    /// Returns its index and the squared distance to it,
+    /// or None if there are no other points.
+    pub fn nearest(&self, p: &Point, skip: Option<Index>) -> Option<(Index, f64)> {
+        let mut best = None;
+        self.search(0, self.order.len(), 0, p, skip, &mut best);
+        best
+    }
+
+    fn search(&self, lo: usize, hi: usize, depth: usize,
+              p: &Point, skip: Option<Index>,
+              best: &mut Option<(Index, f64)>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let ix = self.order[mid];
+        let q = self.points[ix];
+        if Some(ix) != skip {
+            let sqnorm = (q - *p).sqnorm();
+            if best.map_or(true, |(_, best_sqnorm)| sqnorm < best_sqnorm) {
+                *best = Some((ix, sqnorm));
+            }
+        }
+
+        let diff = if depth % 2 == 0 { p.x - q.x } else { p.y - q.y };
+        let (near, far) = if diff < 0. {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.search(near.0, near.1, depth + 1, p, skip, best);
+        // Only look on the far side of the split if it could be closer.
+        if best.map_or(true, |(_, best_sqnorm)| diff * diff < best_sqnorm) {
+            self.search(far.0, far.1, depth + 1, p, skip, best);
+        }
+    }
+}
+
+fn build(points: &Vec<Point>, order: &mut [Index], depth: usize) {
+    if order.len() <= 1 {
+        return;
+    }
+    if depth % 2 == 0 {
+        order.sort_by(|&a, &b| cmp_f64(points[a].x, points[b].x));
+    } else {
+        order.sort_by(|&a, &b| cmp_f64(points[a].y, points[b].y));
+    }
+    let mid = order.len() / 2;
+    build(points, &mut order[..mid], depth + 1);
+    build(points, &mut order[mid + 1..], depth + 1);
+}
+
+#[test]
+fn test_kdtree_nearest() {
+    let points = vec![
+        Point::new(0.0, 0.0), // 0
+        Point::new(1.0, 0.0), // 1
+        Point::new(5.0, 5.0), // 2
+        Point::new(5.0, 6.5), // 3
+        Point::new(-3.0, 2.0), // 4
+    ];
+    let tree = KdTree::new(&points);
+    assert_eq!(tree.nearest(&points[0], Some(0)), Some((1, 1.0)));
+    assert_eq!(tree.nearest(&points[2], Some(2)), Some((3, 2.25)));
+    assert_eq!(tree.nearest(&Point::new(-2.0, 2.0), None), Some((4, 1.0)));
+
+    let lonely = vec![Point::new(1.0, 1.0)];
+    assert_eq!(KdTree::new(&lonely).nearest(&lonely[0], Some(0)), None);
+
+    // A NaN point is never nearest, and doesn't get in the way of the others.
+    let stray = vec![Point::new(0.0, 0.0), Point::new(::std::f64::NAN, 1.0),
+                     Point::new(2.0, 0.0)];
+    assert_eq!(KdTree::new(&stray).nearest(&stray[0], Some(0)), Some((2, 4.0)));
+}
+#[test]
+fn test_kdtree_matches_brute_force() {
+    use input::read_points;
+
+    let points = read_points("datasets/r100.dat").unwrap();
+    let tree = KdTree::new(&points);
+    for i in 0..points.len() {
+        let mut brute = ::std::f64::INFINITY;
+        for j in 0..points.len() {
+            if i != j {
+                brute = brute.min((points[i] - points[j]).sqnorm());
+            }
+        }
+        let (_, sqnorm) = tree.nearest(&points[i], Some(i)).unwrap();
+        assert_eq!(sqnorm, brute);
+    }
+}
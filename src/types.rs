@@ -1,13 +1,9 @@
 
 use na::{Vec2,Vec1,Pnt2,Rot2};
 
-pub type Point = Pnt2<f64>;
+use std::cmp::Ordering;
 
-pub struct SPoint {
-    p: Point,
-    radius: Radius,
-    in_blob: bool,
-}
+pub type Point = Pnt2<f64>;
 
 pub type Vector = Vec2<f64>;
 
@@ -21,6 +17,20 @@ pub type Radius = f64;
 
 pub type Comb = Vec<Vec<Index>>;
 
+/// Orders floats for sorting, with NaN after all the numbers,
+/// so a stray NaN can't make a sort panic.
+pub fn cmp_f64(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+#[test]
+fn test_cmp_f64() {
+    use std::f64::NAN;
+    let mut xs = vec![2., NAN, -1., NAN, 0.5];
+    xs.sort_by(|&a, &b| cmp_f64(a, b));
+    assert_eq!(&xs[..3], &[-1., 0.5, 2.]);
+    assert!(xs[3].is_nan() && xs[4].is_nan());
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Color {
     pub r:f64,
@@ -51,21 +61,3 @@ fn test_hex_code() {
     assert_eq!(c, Color::new(255./255., 0., 0x55 as f64/255.));
 }
 
-
-impl SPoint {
-    fn new(p: Point, r: Radius, inb: bool) -> SPoint {
-        SPoint{ p:p, radius:r, in_blob:inb }
-    }
-}
-
-use std::fmt::{Display, Formatter};
-use std::fmt;
-impl Display for SPoint {
-    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result{
-        if self.in_blob {
-            Display::fmt(&format!("({},{};{})", self.p.x, self.p.y, self.radius), fmt)
-        } else {
-            Display::fmt(&format!("<{},{};{}>", self.p.x, self.p.y, self.radius), fmt)
-        }
-    }
-}
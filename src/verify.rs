@@ -0,0 +1,234 @@
+//! Checking that the blobs we draw really are right: every point of a set
+//! inside its blob, every other point outside, and an outline that makes sense.
+
+use rustc_serialize::json::{Json, ToJson};
+
+use blob;
+use blob::Blob;
+use config::{Config, ConfigProblem};
+use geometry;
+use geometry::Segment;
+use json::object;
+use types::{Point, Index, Comb};
+
+/// Something wrong with a blob.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Problem {
+    /// A point of the set isn't inside the blob.
+    /// distance is how far outside it is, or 0 for on the outline.
+    Outside { point: Index, distance: f64 },
+    /// A point not in the set is inside the blob.
+    Inside { point: Index, distance: f64 },
+    /// The discs of two neighbours on the hull overlap, so there is no
+    /// line tangent to both, and the outline can't be traced at all.
+    Overlap { first: Index, second: Index },
+    /// A line of the outline isn't tangent to the disc of the hull point.
+    NotTangent { point: Index, error: f64 },
+    /// The outline crosses itself near at.
+    Crossing { at: Point },
+}
+
+/// The problems with the set-th blob of the comb-th comb.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlobReport {
+    pub comb: usize,
+    pub set: usize,
+    pub problems: Vec<Problem>,
+}
+
+/// The problems with every blob.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Report {
+    pub blobs: Vec<BlobReport>,
+}
+
+impl Report {
+    /// Whether every blob is right.
+    pub fn is_ok(&self) -> bool {
+        self.blobs.iter().all(|blob| blob.problems.is_empty())
+    }
+}
+
+// How far a line can be from tangent, relative to the radius.
+const TANGENT_TOLERANCE: f64 = 1e-6;
+
+// The outline is checked for crossings as a polyline this close to it,
+// relative to the smallest radius on the hull.
+const CROSSING_TOLERANCE: f64 = 0.01;
+
+/// Finds the blob for every set of every comb, as main does, and checks them.
+pub fn verify(config: &Config, points: &Vec<Point>, combs: &Vec<Comb>)
+        -> Result<Report, ConfigProblem> {
+    let nearest = blob::compute_nearest_distances(points);
+    let mut blobs = vec![];
+    for (comb_num, comb) in combs.iter().enumerate() {
+        for (set_num, set) in comb.iter().enumerate() {
+            let found = try!(blob::find_hull(config, points, set, &nearest));
+            let problems = verify_blob(&found);
+            for problem in &problems {
+                warn!("Comb {} set {}: {:?}", comb_num, set_num, problem);
+            }
+            blobs.push(BlobReport{ comb: comb_num, set: set_num, problems: problems });
+        }
+    }
+    Ok(Report{ blobs: blobs })
+}
+
+/// Everything wrong with blob.
+pub fn verify_blob(blob: &Blob) -> Vec<Problem> {
+    let overlaps = overlaps(blob);
+    if !overlaps.is_empty() {
+        return overlaps;
+    }
+    let segments = blob.segments();
+    let mut problems = vec![];
+
+    for ix in 0..blob.points.len() {
+        let distance = geometry::signed_distance(&segments, &blob.points[ix]);
+        if blob.is_in(ix) && distance >= 0. {
+            problems.push(Problem::Outside{ point: ix, distance: distance });
+        } else if !blob.is_in(ix) && distance <= 0. {
+            problems.push(Problem::Inside{ point: ix, distance: -distance });
+        }
+    }
+
+    // Each hull point has an arc then a line, see geometry::outline.
+    let hull = &blob.hull;
+    for (i, segment) in segments.iter().enumerate() {
+        if let Segment::Line{ from, to } = *segment {
+            let (a, b) = (hull[i / 2], hull[(i / 2 + 1) % hull.len()]);
+            let error = tangent_error(blob.points[a], blob.radii[a], from, to);
+            if !(error <= TANGENT_TOLERANCE) {
+                problems.push(Problem::NotTangent{ point: a, error: error });
+            }
+            let error = tangent_error(blob.points[b], blob.radii[b], from, to);
+            if !(error <= TANGENT_TOLERANCE) {
+                problems.push(Problem::NotTangent{ point: b, error: error });
+            }
+        }
+    }
+
+    let polygon = blob.outline(blob.smallest_radius() * CROSSING_TOLERANCE);
+    let n = polygon.len();
+    for i in 0..n {
+        // Neighbouring edges share a point, so start two along.
+        for j in i + 2..n {
+            if i == 0 && j == n - 1 {
+                continue;
+            }
+            let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+            let (c, d) = (polygon[j], polygon[(j + 1) % n]);
+            if blob::segments_cross(a, b, c, d) {
+                problems.push(Problem::Crossing{ at: a });
+            }
+        }
+    }
+    problems
+}
+
+/// Neighbours on the hull whose discs overlap,
+/// where geometry::smooth_line_normal has nothing to give.
+fn overlaps(blob: &Blob) -> Vec<Problem> {
+    use na::Norm;
+    let hull = &blob.hull;
+    let mut problems = vec![];
+    for i in 0..hull.len() {
+        let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+        let distance = (blob.points[b] - blob.points[a]).norm();
+        let (a_r, b_r) = (blob.radii[a], blob.radii[b]);
+        let needed = if blob.is_in(a) == blob.is_in(b) { (a_r - b_r).abs() } else { a_r + b_r };
+        if !(needed < distance) {
+            problems.push(Problem::Overlap{ first: a, second: b });
+        }
+    }
+    problems
+}
+
+/// How far the line through from and to is from touching the circle,
+/// relative to its radius.
+fn tangent_error(centre: Point, radius: f64, from: Point, to: Point) -> f64 {
+    use na::{Dot, Norm};
+    let along = to - from;
+    let length = along.norm();
+    if length == 0. {
+        // An empty line is tangent if it's on the circle.
+        return ((from - centre).norm() - radius).abs() / radius;
+    }
+    // The distance from the centre to the line.
+    let offset = from - centre;
+    let across = (offset - along * (offset.dot(&along) / (length * length))).norm();
+    (across - radius).abs() / radius
+}
+
+impl ToJson for Problem {
+    fn to_json(&self) -> Json {
+        match *self {
+            Problem::Outside{ point, distance } => object(vec![
+                ("kind", "outside".to_json()), ("point", point.to_json()),
+                ("distance", distance.to_json())]),
+            Problem::Inside{ point, distance } => object(vec![
+                ("kind", "inside".to_json()), ("point", point.to_json()),
+                ("distance", distance.to_json())]),
+            Problem::Overlap{ first, second } => object(vec![
+                ("kind", "overlap".to_json()), ("points", vec![first, second].to_json())]),
+            Problem::NotTangent{ point, error } => object(vec![
+                ("kind", "not_tangent".to_json()), ("point", point.to_json()),
+                ("error", error.to_json())]),
+            Problem::Crossing{ at } => object(vec![
+                ("kind", "crossing".to_json()), ("at", vec![at.x, at.y].to_json())]),
+        }
+    }
+}
+
+impl ToJson for BlobReport {
+    fn to_json(&self) -> Json {
+        object(vec![("comb", self.comb.to_json()), ("set", self.set.to_json()),
+                    ("ok", self.problems.is_empty().to_json()),
+                    ("problems", self.problems.to_json())])
+    }
+}
+
+impl ToJson for Report {
+    fn to_json(&self) -> Json {
+        object(vec![("ok", self.is_ok().to_json()), ("blobs", self.blobs.to_json())])
+    }
+}
+
+#[test]
+fn test_verify_blob() {
+    let points = vec![Point::new(0., 0.), Point::new(1., 0.5), Point::new(2., 0.),
+                      Point::new(1., 2.), Point::new(1., 1.), Point::new(3., 3.)];
+    let mut blob = Blob{ points: &points, hull: vec![0, 3, 2, 1],
+                         inblob: vec![true, false, true, true, true, false],
+                         radii: vec![0.1; 6] };
+    assert!(verify_blob(&blob).is_empty());
+
+    // Leave the middle point out, and it's inside.
+    blob.inblob[4] = false;
+    let problems = verify_blob(&blob);
+    assert_eq!(problems.len(), 1);
+    match problems[0] {
+        Problem::Inside{ point: 4, distance } => assert!(distance > 0.),
+        ref other => panic!("Expected point 4 inside, got {:?}", other),
+    }
+    blob.inblob[4] = true;
+
+    // Discs that swallow their neighbours can't be traced.
+    blob.radii[1] = 1.5;
+    assert_eq!(verify_blob(&blob), vec![Problem::Overlap{ first: 2, second: 1 },
+                                        Problem::Overlap{ first: 1, second: 0 }]);
+}
+#[test]
+fn test_verify_datasets() {
+    use input::{read_points, read_combs};
+
+    let config = Config::default();
+    let points = read_points("datasets/r100.dat").unwrap();
+    for combs_file in &["datasets/merge.txt", "datasets/bad_79.txt"] {
+        let combs = read_combs(combs_file, points.len()).unwrap();
+        let report = verify(&config, &points, &combs).unwrap();
+        let sets = combs.iter().map(|comb| comb.len()).fold(0, |sum, n| sum + n);
+        assert_eq!(report.blobs.len(), sets);
+        assert!(report.is_ok(), "{} has problems: {}", combs_file, report.to_json().pretty());
+    }
+}